@@ -1,14 +1,60 @@
-pub fn add(left: u64, right: u64) -> u64 {
-    left + right
-}
+use proc_macro::TokenStream;
+use syn::{ItemFn, parse_macro_input};
+
+mod projection_sets;
+mod simt_kernel;
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+/// Implements `ProjectionSet` for every tuple of projections up to a given rank
+/// in which each axis of an architecture is contained exactly once.
+///
+/// ```ignore
+/// unsafe_impl_projection_sets!(Nvptx, [X, Y, Z], 3, (<Thread, Grid>, <Thread, Block>));
+/// ```
+///
+/// A projection over several axes is named after its axes in the given order,
+/// e.g. `X` and `Z` are combined to `Xz`. Positions without axes get `()`.
+/// Such combined projections must exist for every subset of the axes.
+///
+/// # Safety
+/// The axes must be every axis of the architecture, each listed exactly once.
+#[proc_macro]
+pub fn unsafe_impl_projection_sets(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as projection_sets::Input);
+    projection_sets::expand(&input).into()
+}
 
-    #[test]
-    fn it_works() {
-        let result = add(2, 2);
-        assert_eq!(result, 4);
-    }
+/// Turns a function into the entry point of a kernel.
+///
+/// ```ignore
+/// #[simt_kernel]
+/// fn scale(
+///     #[grid] #[shape(rows, 64)] data: &mut [f32],
+///     rows: u32,
+///     factor: f32,
+///     shared: &SharedMemory,
+/// ) {
+///     let tile = data.split::<Block, (Xyz, ()), Blocked>().unwrap();
+///     // ...
+/// }
+/// ```
+///
+/// On the GPU the function becomes an unmangled `extern "ptx-kernel"` or
+/// `extern "gpu-kernel"` function, on the host a plain function to call from
+/// `launch`, which needs the `host` feature of `simtarray`. Either way it is `unsafe` to call. GPU crates need
+/// `#![feature(abi_ptx)]` or `#![feature(abi_gpu_kernel)]` respectively.
+///
+/// * `#[grid] name: &mut [T]` is passed as a `DeviceSlice<T>` and turned into a
+///   dense `Grid` scoped `SimtArray`, rank 1 with the length of the slice unless
+///   `#[shape(..)]` lists the dimensions. The dimensions may use other parameters.
+///   The kernel panics if the slice does not fit the shape.
+/// * `name: &Uniform<Sc>` and `name: &SharedMemory` are not passed at all but
+///   created at the entry point, where control flow is still uniform. A kernel
+///   can take only one `SharedMemory`.
+/// * All other parameters are passed unchanged.
+#[proc_macro_attribute]
+pub fn simt_kernel(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let item = parse_macro_input!(item as ItemFn);
+    simt_kernel::expand(attr.into(), item)
+        .unwrap_or_else(|error| error.to_compile_error())
+        .into()
 }
@@ -0,0 +1,152 @@
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::{
+    Ident, LitInt, Result, Token, Type, bracketed, parenthesized,
+    parse::{Parse, ParseStream},
+    punctuated::Punctuated,
+};
+
+pub struct Input {
+    arch: Type,
+    axes: Vec<Ident>,
+    max_rank: usize,
+    scopings: Vec<(Type, Type)>,
+}
+
+impl Parse for Input {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let arch = input.parse()?;
+        input.parse::<Token![,]>()?;
+
+        let content;
+        bracketed!(content in input);
+        let axes: Vec<Ident> = Punctuated::<Ident, Token![,]>::parse_terminated(&content)?
+            .into_iter()
+            .collect();
+        if axes.is_empty() {
+            return Err(content.error("expected at least one axis"));
+        }
+        input.parse::<Token![,]>()?;
+
+        let max_rank = input.parse::<LitInt>()?.base10_parse()?;
+        input.parse::<Token![,]>()?;
+
+        let content;
+        parenthesized!(content in input);
+        let scopings = Punctuated::<Scoping, Token![,]>::parse_terminated(&content)?
+            .into_iter()
+            .map(|scoping| (scoping.scope, scoping.outer))
+            .collect();
+        input.parse::<Option<Token![,]>>()?;
+
+        Ok(Self {
+            arch,
+            axes,
+            max_rank,
+            scopings,
+        })
+    }
+}
+
+/// `<Scope, Outer>`
+struct Scoping {
+    scope: Type,
+    outer: Type,
+}
+
+impl Parse for Scoping {
+    fn parse(input: ParseStream) -> Result<Self> {
+        input.parse::<Token![<]>()?;
+        let scope = input.parse()?;
+        input.parse::<Token![,]>()?;
+        let outer = input.parse()?;
+        input.parse::<Token![>]>()?;
+        Ok(Self { scope, outer })
+    }
+}
+
+pub fn expand(input: &Input) -> TokenStream {
+    let arch = &input.arch;
+    let mut out = TokenStream::new();
+    for rank in 1..=input.max_rank {
+        let dims: Vec<Ident> = (1..=rank).map(|i| format_ident!("D{}", i)).collect();
+        for assignment in assignments(input.axes.len(), rank) {
+            let projections = (0..rank).map(|position| {
+                let axes: Vec<&Ident> = input
+                    .axes
+                    .iter()
+                    .zip(&assignment)
+                    .filter(|(_, p)| **p == position)
+                    .map(|(axis, _)| axis)
+                    .collect();
+                match combined_name(&axes) {
+                    Some(name) => quote!(#name),
+                    None => quote!(()),
+                }
+            });
+            let ty = quote!((#(#projections,)*));
+            for (scope, outer) in &input.scopings {
+                out.extend(quote! {
+                    unsafe impl<#(#dims: Dim),*> ProjectionSet<(#(#dims,)*), #scope, #outer> for #ty
+                    where
+                        (#(#dims,)*): Shape,
+                    {
+                        type Arch = #arch;
+                    }
+                });
+            }
+        }
+    }
+    out
+}
+
+/// Every way to assign each of `axes` axes to one of `rank` positions.
+fn assignments(axes: usize, rank: usize) -> Vec<Vec<usize>> {
+    let mut out = vec![Vec::new()];
+    for _ in 0..axes {
+        out = out
+            .into_iter()
+            .flat_map(|assignment| {
+                (0..rank).map(move |position| {
+                    let mut assignment = assignment.clone();
+                    assignment.push(position);
+                    assignment
+                })
+            })
+            .collect();
+    }
+    out
+}
+
+/// `[X, Y, Z]` -> `Xyz`
+fn combined_name(axes: &[&Ident]) -> Option<Ident> {
+    let (first, rest) = axes.split_first()?;
+    let mut name = first.to_string();
+    for axis in rest {
+        name.push_str(&axis.to_string().to_lowercase());
+    }
+    Some(Ident::new(&name, first.span()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_axis_is_assigned_once() {
+        assert_eq!(assignments(3, 1).len(), 1);
+        assert_eq!(assignments(3, 2).len(), 8);
+        assert_eq!(assignments(3, 3).len(), 27);
+        assert!(assignments(3, 3).iter().all(|a| a.len() == 3));
+    }
+
+    #[test]
+    fn combined_names() {
+        let input: Input = syn::parse_quote!(Nvptx, [X, Y, Z], 2, (<Thread, Block>,));
+        let expanded = expand(&input).to_string();
+        for ty in ["(Xyz , () ,)", "(Yz , X ,)", "(X , Yz ,)", "(() , Xyz ,)"] {
+            assert!(expanded.contains(ty), "missing {ty}");
+        }
+        assert_eq!(expanded.matches("unsafe impl").count(), 1 + 8);
+    }
+}
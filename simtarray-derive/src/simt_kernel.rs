@@ -0,0 +1,323 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{
+    Attribute, Error, Expr, FnArg, GenericArgument, Ident, ItemFn, Pat, PatIdent, PatType,
+    PathArguments, Result, ReturnType, Token, Type, punctuated::Punctuated,
+};
+
+/// A parameter of the kernel after its attributes were taken apart.
+enum Param {
+    /// Passed through unchanged
+    Plain(PatType),
+    /// `#[grid] name: &mut [T]`, optionally with `#[shape(..)]`
+    Grid {
+        ident: Ident,
+        mutability: Option<Token![mut]>,
+        elem: Type,
+        shape: Option<Vec<Expr>>,
+    },
+    /// `name: &Uniform<Sc>` or `name: &SharedMemory`, created in the kernel
+    /// instead of passed in
+    Token { pat: Box<Pat>, ty: Type },
+}
+
+impl Param {
+    fn parse(arg: &FnArg) -> Result<Self> {
+        let FnArg::Typed(arg) = arg else {
+            return Err(Error::new_spanned(arg, "kernels cannot take `self`"));
+        };
+        let mut arg = arg.clone();
+        let mut grid = None;
+        let mut shape = None;
+        let mut attrs = Vec::new();
+        for attr in arg.attrs.drain(..) {
+            if attr.path().is_ident("grid") {
+                attr.meta.require_path_only()?;
+                grid = Some(attr);
+            } else if attr.path().is_ident("shape") {
+                let dims = attr.parse_args_with(Punctuated::<Expr, Token![,]>::parse_terminated)?;
+                if dims.is_empty() {
+                    return Err(Error::new_spanned(attr, "expected at least one dimension"));
+                }
+                shape = Some((attr, dims.into_iter().collect()));
+            } else {
+                attrs.push(attr);
+            }
+        }
+        arg.attrs = attrs;
+
+        match (grid, shape) {
+            (Some(_), shape) => {
+                let Some(elem) = mut_slice_elem(&arg.ty) else {
+                    return Err(Error::new_spanned(&arg.ty, "`#[grid]` expects `&mut [T]`"));
+                };
+                let binding = binding(&arg)?;
+                Ok(Self::Grid {
+                    ident: binding.ident.clone(),
+                    mutability: binding.mutability,
+                    elem: elem.clone(),
+                    shape: shape.map(|(_, dims)| dims),
+                })
+            }
+            (None, Some((attr, _))) => Err(Error::new_spanned(
+                attr,
+                "`#[shape]` is only allowed together with `#[grid]`",
+            )),
+            (None, None) => match &*arg.ty {
+                Type::Reference(r)
+                    if r.mutability.is_none()
+                        && (is_named(&r.elem, "Uniform") || is_named(&r.elem, "SharedMemory")) =>
+                {
+                    binding(&arg)?;
+                    Ok(Self::Token {
+                        pat: arg.pat.clone(),
+                        ty: (*r.elem).clone(),
+                    })
+                }
+                _ => Ok(Self::Plain(arg)),
+            },
+        }
+    }
+}
+
+/// `[T]` of `&mut [T]`
+fn mut_slice_elem(ty: &Type) -> Option<&Type> {
+    match ty {
+        Type::Reference(r) if r.mutability.is_some() => match &*r.elem {
+            Type::Slice(slice) => Some(&slice.elem),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Whether the type is a path that ends in `name` or `name<..>`
+fn is_named(ty: &Type, name: &str) -> bool {
+    match ty {
+        Type::Path(path) if path.qself.is_none() => path
+            .path
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == name),
+        _ => false,
+    }
+}
+
+/// Whether the type is `Uniform<CooperativeGrid>`, whose kernels must be
+/// launched cooperatively
+fn is_cooperative(ty: &Type) -> bool {
+    let Type::Path(path) = ty else {
+        return false;
+    };
+    let Some(segment) = path.path.segments.last() else {
+        return false;
+    };
+    let PathArguments::AngleBracketed(args) = &segment.arguments else {
+        return false;
+    };
+    args.args.iter().any(|arg| match arg {
+        GenericArgument::Type(Type::Path(scope)) => scope
+            .path
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == "CooperativeGrid"),
+        _ => false,
+    })
+}
+
+/// Parameters the macro rewrites must bind a plain name.
+fn binding(arg: &PatType) -> Result<&PatIdent> {
+    match &*arg.pat {
+        Pat::Ident(ident) if ident.by_ref.is_none() && ident.subpat.is_none() => Ok(ident),
+        pat => Err(Error::new_spanned(pat, "expected an identifier")),
+    }
+}
+
+pub fn expand(attr: TokenStream, item: ItemFn) -> Result<TokenStream> {
+    if !attr.is_empty() {
+        return Err(Error::new_spanned(
+            attr,
+            "`#[simt_kernel]` takes no arguments",
+        ));
+    }
+    let ItemFn {
+        attrs,
+        vis,
+        sig,
+        block,
+    } = item;
+    if !sig.generics.params.is_empty() || sig.generics.where_clause.is_some() {
+        return Err(Error::new_spanned(
+            &sig.generics,
+            "kernels cannot be generic",
+        ));
+    }
+    if let Some(asyncness) = sig.asyncness {
+        return Err(Error::new_spanned(asyncness, "kernels cannot be async"));
+    }
+    if let Some(abi) = &sig.abi {
+        return Err(Error::new_spanned(
+            abi,
+            "the ABI is chosen by `#[simt_kernel]`",
+        ));
+    }
+    if let Some(variadic) = &sig.variadic {
+        return Err(Error::new_spanned(variadic, "kernels cannot be variadic"));
+    }
+    if let ReturnType::Type(..) = sig.output {
+        return Err(Error::new_spanned(
+            &sig.output,
+            "kernels cannot return a value",
+        ));
+    }
+
+    let mut params = Vec::new();
+    let mut prelude = Vec::new();
+    let mut cooperative = false;
+    let mut shared = false;
+    for arg in &sig.inputs {
+        match Param::parse(arg)? {
+            Param::Plain(arg) => params.push(quote!(#arg)),
+            Param::Grid {
+                ident,
+                mutability,
+                elem,
+                shape,
+            } => {
+                let shape = match shape {
+                    Some(dims) => quote!((#(::simtarray::kernel::dim(#dims),)*)),
+                    None => quote!((#ident.len(),)),
+                };
+                params.push(quote!(#ident: ::simtarray::DeviceSlice<'_, #elem>));
+                // The shape may read the slice, so it is evaluated before the
+                // slice is moved into the array
+                prelude.push(quote! {
+                    let #mutability #ident = {
+                        let __shape = #shape;
+                        ::simtarray::kernel::grid_array(::core::stringify!(#ident), #ident, __shape)
+                    };
+                });
+            }
+            Param::Token { pat, ty } => {
+                // A second token would hand out every shared buffer again
+                if is_named(&ty, "SharedMemory") && std::mem::replace(&mut shared, true) {
+                    return Err(Error::new_spanned(
+                        pat,
+                        "a kernel can only take one `SharedMemory`",
+                    ));
+                }
+                cooperative |= is_cooperative(&ty);
+                prelude.push(quote!(let #pat: &#ty = &unsafe { <#ty>::new() };));
+            }
+        }
+    }
+
+    let name = &sig.ident;
+    let body = quote!({
+        #(#prelude)*
+        #block
+    });
+    let launch = cooperative.then(|| quote!(#[doc = " The launch must be cooperative."]));
+    let kernel = |abi: TokenStream, extra: &[Attribute]| {
+        quote! {
+            #(#attrs)*
+            ///
+            /// # Safety
+            /// Must be called by every thread of a launch with the same arguments.
+            /// The slices must be valid for the whole launch and only be accessed
+            /// through this kernel.
+            #launch
+            #(#extra)*
+            #vis unsafe #abi fn #name(#(#params),*) #body
+        }
+    };
+    let no_mangle: Attribute = syn::parse_quote!(#[unsafe(no_mangle)]);
+    let nvptx = kernel(
+        quote!(extern "ptx-kernel"),
+        core::slice::from_ref(&no_mangle),
+    );
+    let amdgpu = kernel(
+        quote!(extern "gpu-kernel"),
+        core::slice::from_ref(&no_mangle),
+    );
+    let host = kernel(TokenStream::new(), &[]);
+    Ok(quote! {
+        #[cfg(target_arch = "nvptx64")]
+        #nvptx
+        #[cfg(target_arch = "amdgpu")]
+        #amdgpu
+        #[cfg(not(any(target_arch = "nvptx64", target_arch = "amdgpu")))]
+        #host
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expand_str(item: ItemFn) -> Result<String> {
+        expand(TokenStream::new(), item).map(|tokens| tokens.to_string())
+    }
+
+    #[test]
+    fn rewrites_parameters() {
+        let expanded = expand_str(syn::parse_quote! {
+            fn kernel(
+                #[grid] #[shape(n, 4)] data: &mut [f32],
+                n: u32,
+                uniform: &Uniform<Block>,
+                shared: &SharedMemory,
+            ) {}
+        })
+        .unwrap();
+        assert!(expanded.contains("extern \"ptx-kernel\" fn kernel"));
+        assert!(expanded.contains("extern \"gpu-kernel\" fn kernel"));
+        assert!(expanded.contains("(data : :: simtarray :: DeviceSlice < '_ , f32 > , n : u32)"));
+        assert!(
+            expanded.contains(
+                "(:: simtarray :: kernel :: dim (n) , :: simtarray :: kernel :: dim (4) ,)"
+            )
+        );
+        assert!(expanded.contains("let uniform : & Uniform < Block > = & unsafe"));
+        assert!(expanded.contains("let shared : & SharedMemory = & unsafe"));
+        assert!(!expanded.contains("cooperative"));
+    }
+
+    #[test]
+    fn documents_cooperative_launch() {
+        let expanded = expand_str(syn::parse_quote! {
+            fn kernel(uniform: &Uniform<CooperativeGrid>) {}
+        })
+        .unwrap();
+        assert!(expanded.contains("The launch must be cooperative."));
+    }
+
+    #[test]
+    fn rejects_invalid_kernels() {
+        let errors = [
+            syn::parse_quote!(
+                fn kernel<T>() {}
+            ),
+            syn::parse_quote!(
+                fn kernel() -> u32 {
+                    0
+                }
+            ),
+            syn::parse_quote!(
+                fn kernel(#[grid] data: &[f32]) {}
+            ),
+            syn::parse_quote!(
+                fn kernel(#[shape(4)] data: &mut [f32]) {}
+            ),
+            syn::parse_quote!(
+                fn kernel(#[grid] (a, b): &mut [f32]) {}
+            ),
+            syn::parse_quote!(
+                fn kernel(a: &SharedMemory, b: &SharedMemory) {}
+            ),
+        ];
+        for item in errors {
+            assert!(expand_str(item).is_err());
+        }
+    }
+}
@@ -1 +1,188 @@
+use core::sync::atomic::{Ordering, fence};
 
+use mdarray::Dim;
+use simtarray_derive::unsafe_impl_projection_sets;
+
+use crate::size_type::_32Bit;
+
+use super::macros::*;
+use super::*;
+use core::arch::amdgpu::*;
+use core::intrinsics::gpu::amdgpu_dispatch_ptr;
+
+pub struct Amdgpu;
+
+impl Arch for Amdgpu {
+    type Scope<S: Scope<Arch = Self>> = S;
+
+    type IndexSize = _32Bit;
+}
+
+pub struct Grid;
+/// The grid of a cooperative launch, see [`CooperativeScope`].
+pub struct CooperativeGrid;
+pub struct Workgroup;
+pub struct Workitem;
+
+impl_scope!(Amdgpu, (Grid, CooperativeGrid, Workgroup, Workitem));
+
+impl UnitScope for Workitem {}
+
+impl SyncableScope for Workgroup {
+    #[inline]
+    unsafe fn sync() {
+        fence(Ordering::Release);
+        s_barrier();
+        fence(Ordering::Acquire);
+    }
+}
+
+/// Arrival counter of the grid barrier. Lives in global memory and is zero
+/// initialized when the code object is loaded. It is shared by all cooperative
+/// launches of the code object, see [`CooperativeScope`] for when it must be reset.
+#[unsafe(export_name = "SIMTARRAY_GRID_BARRIER")]
+static GRID_BARRIER: core::sync::atomic::AtomicU32 = core::sync::atomic::AtomicU32::new(0);
+
+impl SyncableScope for CooperativeGrid {
+    #[inline]
+    unsafe fn sync() {
+        unsafe {
+            Workgroup::sync();
+            if workitem_id_x() == 0 && workitem_id_y() == 0 && workitem_id_z() == 0 {
+                let blocks = num_workgroups(0) * num_workgroups(1) * num_workgroups(2);
+                let first_block =
+                    workgroup_id_x() == 0 && workgroup_id_y() == 0 && workgroup_id_z() == 0;
+                grid_barrier(&GRID_BARRIER, blocks, first_block);
+            }
+            Workgroup::sync();
+        }
+    }
+}
+
+impl CooperativeScope for CooperativeGrid {
+    type Grid = Grid;
+}
+
+/// The leading fields of `hsa_kernel_dispatch_packet_t`.
+#[allow(dead_code)]
+#[repr(C)]
+struct DispatchPacket {
+    header: u16,
+    setup: u16,
+    workgroup_size: [u16; 3],
+    reserved0: u16,
+    grid_size: [u32; 3],
+}
+
+#[inline]
+fn dispatch_packet() -> &'static DispatchPacket {
+    // SAFETY: The dispatch packet of the running kernel stays valid while it
+    // executes
+    unsafe { &*(amdgpu_dispatch_ptr() as *const DispatchPacket) }
+}
+
+#[inline]
+fn workgroup_size(dim: usize) -> u32 {
+    dispatch_packet().workgroup_size[dim] as u32
+}
+
+/// Extent of the grid in workitems
+#[inline]
+fn grid_size(dim: usize) -> u32 {
+    dispatch_packet().grid_size[dim]
+}
+
+/// Extent of the workgroup with the given id in workitems. Only the last
+/// workgroup along a dimension can be smaller than `workgroup_size`.
+#[inline]
+fn local_size(dim: usize, workgroup_id: u32) -> u32 {
+    let size = workgroup_size(dim);
+    size.min(grid_size(dim) - workgroup_id * size)
+}
+
+/// Extent of the grid in workgroups. The last workgroup may be partial.
+#[inline]
+fn num_workgroups(dim: usize) -> u32 {
+    grid_size(dim).div_ceil(workgroup_size(dim))
+}
+
+pub struct X;
+pub struct Y;
+pub struct Z;
+pub struct Xy;
+pub struct Xz;
+pub struct Xyz;
+pub struct Yz;
+
+impl_projection!(
+    <Workitem, Workgroup> for X => { Amdgpu, X, () },
+    { local_size(0, workgroup_id_x()) },
+    { workitem_id_x() }
+);
+impl_projection!(
+    <Workitem, Workgroup> for Y => { Amdgpu, Y, () },
+    { local_size(1, workgroup_id_y()) },
+    { workitem_id_y() }
+);
+impl_projection!(
+    <Workitem, Workgroup> for Z => { Amdgpu, Z, () },
+    { local_size(2, workgroup_id_z()) },
+    { workitem_id_z() }
+);
+
+/// Implements the projections into a grid, which are the same for a
+/// cooperative one.
+macro_rules! impl_grid_projections {
+    ($($grid:ty),+) => {
+        $(
+            impl_projection!(
+                <Workitem, $grid> for X => { Amdgpu, X, () },
+                { grid_size(0) },
+                { workgroup_id_x() * workgroup_size(0) + workitem_id_x() }
+            );
+            impl_projection!(
+                <Workitem, $grid> for Y => { Amdgpu, Y, () },
+                { grid_size(1) },
+                { workgroup_id_y() * workgroup_size(1) + workitem_id_y() }
+            );
+            impl_projection!(
+                <Workitem, $grid> for Z => { Amdgpu, Z, () },
+                { grid_size(2) },
+                { workgroup_id_z() * workgroup_size(2) + workitem_id_z() }
+            );
+            impl_projection!(
+                <Workgroup, $grid> for X => { Amdgpu, X, () },
+                { num_workgroups(0) },
+                { workgroup_id_x() }
+            );
+            impl_projection!(
+                <Workgroup, $grid> for Y => { Amdgpu, Y, () },
+                { num_workgroups(1) },
+                { workgroup_id_y() }
+            );
+            impl_projection!(
+                <Workgroup, $grid> for Z => { Amdgpu, Z, () },
+                { num_workgroups(2) },
+                { workgroup_id_z() }
+            );
+            impl_projections!(<Workitem,$grid> for Xy, Xz, Yz, Xyz => {
+                Amdgpu, (X, X, Y, X), (Y, Z, Z, Yz)
+            });
+            impl_projections!(<Workgroup,$grid> for Xy, Xz, Yz, Xyz => {
+                Amdgpu, (X, X, Y, X), (Y, Z, Z, Yz)
+            });
+        )+
+    };
+}
+
+impl_grid_projections!(Grid, CooperativeGrid);
+
+impl_projections!(<Workitem,Workgroup> for Xy, Xz, Yz, Xyz => {Amdgpu, (X, X, Y, X), (Y, Z, Z, Yz)});
+
+unsafe_impl_projection_sets!(Amdgpu, [X, Y, Z], 3, (
+    <Workitem, Grid>,
+    <Workitem, Workgroup>,
+    <Workgroup, Grid>,
+    <Workitem, CooperativeGrid>,
+    <Workgroup, CooperativeGrid>,
+));
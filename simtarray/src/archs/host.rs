@@ -0,0 +1,554 @@
+//! Emulation of a SIMT architecture on the host CPU.
+//!
+//! Every thread of a kernel launch is run on its own OS thread, so kernels
+//! written against the generic [`Scope`]s can be exercised in ordinary tests.
+use std::{
+    alloc::{self, Layout as Alloc},
+    boxed::Box,
+    cell::RefCell,
+    panic::{self, AssertUnwindSafe},
+    sync::{Arc, Condvar, Mutex},
+    thread,
+    vec::Vec,
+};
+
+use mdarray::{Dense, DenseMapping, Dim};
+use simtarray_derive::unsafe_impl_projection_sets;
+
+use crate::{AsyncCopyScope, Uninit, size_type::_32Bit};
+
+use super::macros::*;
+use super::*;
+
+pub struct Host;
+
+impl Arch for Host {
+    type Scope<S: Scope<Arch = Self>> = S;
+
+    type IndexSize = _32Bit;
+}
+
+pub struct Grid;
+/// The grid of a cooperative launch, see [`CooperativeScope`].
+pub struct CooperativeGrid;
+pub struct Block;
+pub struct Thread;
+
+impl_scope!(Host, (Grid, CooperativeGrid, Block, Thread));
+
+impl UnitScope for Thread {}
+
+impl SyncableScope for Block {
+    #[inline]
+    unsafe fn sync() {
+        context(|ctx| ctx.block_barrier.clone()).wait();
+    }
+}
+
+/// Copies complete immediately, waiting only leaves the synchronization.
+unsafe impl AsyncCopyScope for Block {
+    #[inline]
+    unsafe fn copy<T: Copy>(dst: *mut T, src: *const T) {
+        unsafe { dst.write(src.read()) }
+    }
+    #[inline]
+    unsafe fn commit() {}
+    #[inline]
+    fn wait_group<const N: u32>() {}
+}
+
+/// All threads of a host launch run concurrently, so every launch behaves like a
+/// cooperative one.
+impl SyncableScope for CooperativeGrid {
+    #[inline]
+    unsafe fn sync() {
+        context(|ctx| ctx.grid_barrier.clone()).wait();
+    }
+}
+
+impl CooperativeScope for CooperativeGrid {
+    type Grid = Grid;
+}
+
+/// Extents of a grid or of a block in threads.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Dim3 {
+    pub x: u32,
+    pub y: u32,
+    pub z: u32,
+}
+
+impl Dim3 {
+    fn len(self) -> usize {
+        self.x as usize * self.y as usize * self.z as usize
+    }
+    fn unravel(self, idx: usize) -> Self {
+        let idx = idx as u32;
+        Self {
+            x: idx % self.x,
+            y: idx / self.x % self.y,
+            z: idx / (self.x * self.y),
+        }
+    }
+}
+
+impl From<u32> for Dim3 {
+    fn from(x: u32) -> Self {
+        Self { x, y: 1, z: 1 }
+    }
+}
+
+impl From<(u32, u32)> for Dim3 {
+    fn from((x, y): (u32, u32)) -> Self {
+        Self { x, y, z: 1 }
+    }
+}
+
+impl From<(u32, u32, u32)> for Dim3 {
+    fn from((x, y, z): (u32, u32, u32)) -> Self {
+        Self { x, y, z }
+    }
+}
+
+#[derive(Clone)]
+struct Context {
+    grid_dim: Dim3,
+    block_dim: Dim3,
+    block_idx: Dim3,
+    thread_idx: Dim3,
+    block_barrier: Arc<Barrier>,
+    grid_barrier: Arc<Barrier>,
+    shared: Arc<SharedBuffers>,
+}
+
+/// The buffers of the `shared_array!` sites of a block, keyed by the address
+/// of a static of the site. They are freed after the launch.
+#[derive(Default)]
+struct SharedBuffers(Mutex<Vec<(usize, *mut u8, Alloc)>>);
+
+// The buffers are only handed out as pointers, accessing them is up to the
+// kernel
+unsafe impl Send for SharedBuffers {}
+unsafe impl Sync for SharedBuffers {}
+
+impl Drop for SharedBuffers {
+    fn drop(&mut self) {
+        for &(_, ptr, layout) in self.0.get_mut().unwrap().iter() {
+            unsafe { alloc::dealloc(ptr, layout) };
+        }
+    }
+}
+
+/// A barrier of a launch. It is poisoned when a thread of the launch panics,
+/// which wakes up the waiting threads instead of leaving them blocked forever.
+struct Barrier {
+    threads: usize,
+    state: Mutex<BarrierState>,
+    arrived: Condvar,
+}
+
+#[derive(Default)]
+struct BarrierState {
+    count: usize,
+    generation: usize,
+    poisoned: bool,
+}
+
+/// Unwinds the threads that were woken up by a poisoned barrier. `launch`
+/// re-raises the panic that poisoned it instead.
+struct Poisoned;
+
+impl Barrier {
+    fn new(threads: usize) -> Self {
+        Self {
+            threads,
+            state: Mutex::default(),
+            arrived: Condvar::new(),
+        }
+    }
+
+    fn wait(&self) {
+        let mut state = self.state.lock().unwrap();
+        let generation = state.generation;
+        state.count += 1;
+        if state.count < self.threads {
+            state = self
+                .arrived
+                .wait_while(state, |s| s.generation == generation && !s.poisoned)
+                .unwrap();
+        } else {
+            state.count = 0;
+            state.generation = state.generation.wrapping_add(1);
+            self.arrived.notify_all();
+        }
+        if state.poisoned {
+            drop(state);
+            panic::resume_unwind(Box::new(Poisoned));
+        }
+    }
+
+    fn poison(&self) {
+        self.state.lock().unwrap().poisoned = true;
+        self.arrived.notify_all();
+    }
+}
+
+std::thread_local! {
+    static CONTEXT: RefCell<Option<Context>> = const { RefCell::new(None) };
+}
+
+fn context<R>(f: impl FnOnce(&Context) -> R) -> R {
+    CONTEXT.with_borrow(|ctx| {
+        f(ctx
+            .as_ref()
+            .expect("not called inside of a host kernel launch"))
+    })
+}
+
+/// Runs `kernel` once for every thread of a `grid` of `block`s.
+///
+/// All threads run concurrently on their own OS thread, so `Block::sync` behaves
+/// like a barrier of a real device. The function returns when every thread has
+/// finished.
+///
+/// # Panics
+/// Panics with the panic of the first thread of the kernel that panics. The
+/// barriers of the launch are poisoned then, so that the other threads do not
+/// wait for it forever.
+pub fn launch<F>(grid: impl Into<Dim3>, block: impl Into<Dim3>, kernel: F)
+where
+    F: Fn() + Sync,
+{
+    let grid_dim = grid.into();
+    let block_dim = block.into();
+    let kernel = &kernel;
+    thread::scope(|s| {
+        let mut handles = Vec::with_capacity(grid_dim.len() * block_dim.len());
+        let grid_barrier = Arc::new(Barrier::new(grid_dim.len() * block_dim.len()));
+        for block in 0..grid_dim.len() {
+            let block_barrier = Arc::new(Barrier::new(block_dim.len()));
+            let shared = Arc::new(SharedBuffers::default());
+            for thread in 0..block_dim.len() {
+                let ctx = Context {
+                    grid_dim,
+                    block_dim,
+                    block_idx: grid_dim.unravel(block),
+                    thread_idx: block_dim.unravel(thread),
+                    block_barrier: block_barrier.clone(),
+                    grid_barrier: grid_barrier.clone(),
+                    shared: shared.clone(),
+                };
+                handles.push(s.spawn(move || {
+                    let (block_barrier, grid_barrier) =
+                        (ctx.block_barrier.clone(), ctx.grid_barrier.clone());
+                    CONTEXT.set(Some(ctx));
+                    let result = panic::catch_unwind(AssertUnwindSafe(kernel));
+                    CONTEXT.set(None);
+                    if result.is_err() {
+                        block_barrier.poison();
+                        grid_barrier.poison();
+                    }
+                    result
+                }));
+            }
+        }
+        let mut first = None;
+        for handle in handles {
+            // The kernel's panics are caught in the thread
+            if let Err(payload) = handle.join().unwrap()
+                && !payload.is::<Poisoned>()
+            {
+                first.get_or_insert(payload);
+            }
+        }
+        if let Some(payload) = first {
+            panic::resume_unwind(payload);
+        }
+    });
+}
+
+/// Host emulation of the `shared_array!` of a device, see there. Each block
+/// of a launch gets its own buffer per expansion site.
+///
+/// # Panics
+/// Every expansion site returns its buffer only once per kernel. Evaluating it
+/// again with the same token, e.g. in a loop, panics.
+#[macro_export]
+macro_rules! shared_array {
+    ($shared:expr; $T:ty; $($N:expr),+ $(,)?) => {
+        $crate::shared_array!($shared; $T; $($N),+; align = ::core::mem::align_of::<$T>())
+    };
+    ($shared:expr; $T:ty; $($N:expr),+; align = $align:expr) => {{
+        type Shape = ($($crate::mdarray::Const<{ $N }>,)+);
+        const LEN: usize = 1 $(* $N)+;
+        const ALIGN: usize = $align;
+        const {
+            assert!(LEN > 0, "shared arrays must not be empty");
+            assert!(
+                ALIGN.is_power_of_two() && ALIGN >= ::core::mem::align_of::<$T>(),
+                "alignment must be a power of two of at least that of the element type"
+            );
+        }
+        // Has interior mutability, so that every site has its own address
+        static SITE: ::core::sync::atomic::AtomicU8 = ::core::sync::atomic::AtomicU8::new(0);
+        let shared: &$crate::SharedMemory = $shared;
+        // SAFETY: Size and alignment are those of this site
+        unsafe {
+            $crate::host::shared_array_at::<$T, Shape>(
+                &SITE as *const _ as usize,
+                LEN * ::core::mem::size_of::<$T>(),
+                ALIGN,
+                shared,
+            )
+        }
+    }};
+}
+
+/// The array of the `shared_array!` site `site` in the calling block, which is
+/// allocated by the first thread that gets here.
+///
+/// # Safety
+/// `site` must identify a site of [`shared_array!`], `size` and `align` must be
+/// those of the site and hold `Sh`.
+#[doc(hidden)]
+pub unsafe fn shared_array_at<'s, T, Sh: Shape + Default>(
+    site: usize,
+    size: usize,
+    align: usize,
+    shared: &'s crate::SharedMemory,
+) -> SimtArray<'s, T, Block, Uninit, Dense, Sh> {
+    shared.take(site);
+    let layout = Alloc::from_size_align(size, align).unwrap();
+    let ptr = context(|ctx| {
+        let mut buffers = ctx.shared.0.lock().unwrap();
+        match buffers.iter().find(|&&(key, ..)| key == site) {
+            Some(&(_, ptr, _)) => ptr,
+            None => {
+                let ptr = unsafe { alloc::alloc(layout) };
+                assert!(!ptr.is_null(), "out of memory");
+                buffers.push((site, ptr, layout));
+                ptr
+            }
+        }
+    });
+    unsafe { SimtArray::new_unchecked(ptr.cast(), DenseMapping::new(Sh::default())) }
+}
+
+pub struct X;
+pub struct Y;
+pub struct Z;
+pub struct Xy;
+pub struct Xz;
+pub struct Xyz;
+pub struct Yz;
+
+impl_projection!(
+    <Thread, Block> for X => { Host, X, () },
+    { context(|ctx| ctx.block_dim.x) },
+    { context(|ctx| ctx.thread_idx.x) }
+);
+impl_projection!(
+    <Thread, Block> for Y => { Host, Y, () },
+    { context(|ctx| ctx.block_dim.y) },
+    { context(|ctx| ctx.thread_idx.y) }
+);
+impl_projection!(
+    <Thread, Block> for Z => { Host, Z, () },
+    { context(|ctx| ctx.block_dim.z) },
+    { context(|ctx| ctx.thread_idx.z) }
+);
+/// Implements the projections into a grid, which are the same for a
+/// cooperative one.
+macro_rules! impl_grid_projections {
+    ($($grid:ty),+) => {
+        $(
+            impl_projection!(
+                <Thread, $grid> for X => { Host, X, () },
+                { context(|ctx| ctx.block_dim.x * ctx.grid_dim.x) },
+                { context(|ctx| ctx.block_dim.x * ctx.block_idx.x + ctx.thread_idx.x) }
+            );
+            impl_projection!(
+                <Thread, $grid> for Y => { Host, Y, () },
+                { context(|ctx| ctx.block_dim.y * ctx.grid_dim.y) },
+                { context(|ctx| ctx.block_dim.y * ctx.block_idx.y + ctx.thread_idx.y) }
+            );
+            impl_projection!(
+                <Thread, $grid> for Z => { Host, Z, () },
+                { context(|ctx| ctx.block_dim.z * ctx.grid_dim.z) },
+                { context(|ctx| ctx.block_dim.z * ctx.block_idx.z + ctx.thread_idx.z) }
+            );
+            impl_projection!(
+                <Block, $grid> for X => { Host, X, () },
+                { context(|ctx| ctx.grid_dim.x) },
+                { context(|ctx| ctx.block_idx.x) }
+            );
+            impl_projection!(
+                <Block, $grid> for Y => { Host, Y, () },
+                { context(|ctx| ctx.grid_dim.y) },
+                { context(|ctx| ctx.block_idx.y) }
+            );
+            impl_projection!(
+                <Block, $grid> for Z => { Host, Z, () },
+                { context(|ctx| ctx.grid_dim.z) },
+                { context(|ctx| ctx.block_idx.z) }
+            );
+            impl_projections!(<Thread,$grid> for Xy, Xz, Yz, Xyz => {
+                Host, (X, X, Y, X), (Y, Z, Z, Yz)
+            });
+            impl_projections!(<Block,$grid> for Xy, Xz, Yz, Xyz => {
+                Host, (X, X, Y, X), (Y, Z, Z, Yz)
+            });
+        )+
+    };
+}
+
+impl_grid_projections!(Grid, CooperativeGrid);
+
+impl_projections!(<Thread,Block> for Xy, Xz, Yz, Xyz => {Host, (X, X, Y, X), (Y, Z, Z, Yz)});
+
+unsafe_impl_projection_sets!(Host, [X, Y, Z], 3, (
+    <Thread, Grid>,
+    <Thread, Block>,
+    <Block, Grid>,
+    <Thread, CooperativeGrid>,
+    <Block, CooperativeGrid>,
+));
+
+/// Memory that every thread of a test launch can access, like a buffer in
+/// device memory.
+#[cfg(test)]
+pub(crate) struct Buffer<T> {
+    ptr: *mut T,
+    len: usize,
+}
+
+#[cfg(test)]
+unsafe impl<T: Send> Sync for Buffer<T> {}
+
+#[cfg(test)]
+impl<T> Buffer<T> {
+    pub(crate) fn new(data: Vec<T>) -> Self {
+        let len = data.len();
+        let ptr = std::boxed::Box::into_raw(data.into_boxed_slice()).cast();
+        Self { ptr, len }
+    }
+    pub(crate) fn ptr(&self) -> *mut T {
+        self.ptr
+    }
+    /// A dense array over the elements starting at `offset`, which the
+    /// shape must fit into.
+    pub(crate) fn array<Sc: Scope, I: crate::State, Sh: Shape>(
+        &self,
+        offset: usize,
+        shape: Sh,
+    ) -> crate::SimtArray<'_, T, Sc, I, mdarray::Dense, Sh> {
+        let mapping = mdarray::DenseMapping::new(shape);
+        assert!(offset + mdarray::Mapping::len(&mapping) <= self.len);
+        unsafe { crate::SimtArray::new_unchecked(self.ptr.add(offset), mapping) }
+    }
+    pub(crate) fn into_vec(self) -> Vec<T> {
+        let this = core::mem::ManuallyDrop::new(self);
+        let slice = core::ptr::slice_from_raw_parts_mut(this.ptr, this.len);
+        unsafe { std::boxed::Box::from_raw(slice) }.into_vec()
+    }
+}
+
+#[cfg(test)]
+impl<T> Drop for Buffer<T> {
+    fn drop(&mut self) {
+        let slice = core::ptr::slice_from_raw_parts_mut(self.ptr, self.len);
+        drop(unsafe { std::boxed::Box::from_raw(slice) });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec;
+
+    use super::*;
+
+    #[test]
+    fn projections() {
+        let seen = Buffer::new(vec![0u32; 24]);
+        launch((2, 1, 1), (3, 2, 2), || {
+            let dim = <Xyz as Projection<Thread, Grid>>::dim();
+            let idx = <Xyz as Projection<Thread, Grid>>::idx();
+            assert_eq!(dim, 24);
+            assert_eq!(<Xy as Projection<Block, Grid>>::dim(), 2);
+            assert_eq!(<Xyz as Projection<Thread, Block>>::dim(), 12);
+            unsafe { *seen.ptr().add(idx as usize) += 1 };
+        });
+        assert!(seen.into_vec().iter().all(|&n| n == 1));
+    }
+
+    #[test]
+    fn grid_sync() {
+        let data = Buffer::new(vec![0u32; 8]);
+        launch(4, 2, || {
+            let uniform = unsafe { crate::Uniform::<CooperativeGrid>::new() };
+            let array = data
+                .array::<Grid, crate::Uninit, _>(0, (8,))
+                .into_cooperative(&uniform);
+            let mut array = unsafe { array.init_with::<Thread, (Xyz,), crate::Cyclic, _>(|_| 0) };
+            let idx = <Xyz as Projection<Thread, CooperativeGrid>>::idx();
+            {
+                let mut part = unsafe { array.view_mut::<Thread, (Xyz,), crate::Cyclic>() };
+                part.expr_mut().unwrap()[[0]] = idx + 1;
+            }
+            let sum: u32 = (0..8).map(|i| array.expr()[[i]]).sum();
+            assert_eq!(sum, 36);
+        });
+        assert_eq!(data.into_vec(), (1..=8).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn panic_poisons_barriers() {
+        let result = panic::catch_unwind(|| {
+            launch(2, 4, || {
+                if <Xyz as Projection<Thread, Grid>>::idx() == 5 {
+                    panic!("kernel failed");
+                }
+                unsafe {
+                    Block::sync();
+                    CooperativeGrid::sync();
+                }
+            })
+        });
+        let payload = result.unwrap_err();
+        assert_eq!(payload.downcast_ref::<&str>(), Some(&"kernel failed"));
+    }
+
+    #[test]
+    fn shared_array() {
+        let data = Buffer::new(vec![0u32; 8]);
+        launch(2, 4, || {
+            let shared = unsafe { crate::SharedMemory::new() };
+            let block = <Xyz as Projection<Block, Grid>>::idx();
+            let tile = shared_array!(&shared; u32; 4);
+            let tile = unsafe {
+                tile.init_with::<Thread, (Xyz,), crate::Cyclic, _>(|(crate::GlobalIndex(i),)| {
+                    block * 4 + i as u32
+                })
+            };
+            let reversed = data.array::<Grid, crate::Uninit, _>(0, (8,));
+            let reversed = reversed.split::<Block, (Xyz,), crate::Blocked>().unwrap();
+            unsafe {
+                reversed.init_with::<Thread, (Xyz,), crate::Cyclic, _>(|(crate::GlobalIndex(i),)| {
+                    tile.expr()[[3 - i]]
+                })
+            };
+        });
+        assert_eq!(data.into_vec(), [3, 2, 1, 0, 7, 6, 5, 4]);
+    }
+
+    #[test]
+    #[should_panic(expected = "evaluated twice")]
+    fn shared_array_site_twice() {
+        launch(1, 2, || {
+            let shared = unsafe { crate::SharedMemory::new() };
+            for _ in 0..2 {
+                let _tile = shared_array!(&shared; u32; 4);
+            }
+        });
+    }
+}
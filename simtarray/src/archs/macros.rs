@@ -10,39 +10,6 @@ macro_rules! impl_scope {
 
 pub(crate) use impl_scope;
 
-macro_rules! unsafe_impl_projection_set {
-    ($arch:ty, ($($dim:ident),+$(,)?), $scope:ty, $in:ty, $type:ty) => {
-        unsafe impl<$($dim: Dim),+> ProjectionSet<($($dim),+,), $scope, $in> for $type
-        where
-            ($($dim),+,): Shape,
-        {
-            type Arch = $arch;
-        }
-    }
-}
-
-pub(crate) use unsafe_impl_projection_set;
-
-macro_rules! unsafe_impl_projection_sets {
-    ($arch:ty, $dim:tt, $scoping:tt, {$($type:ty),+$(,)?}) => {
-        $(
-            unsafe_impl_projection_sets_inner!($arch, $dim, $scoping, $type);
-        )+
-    }
-}
-
-pub(crate) use unsafe_impl_projection_sets;
-
-macro_rules! unsafe_impl_projection_sets_inner {
-    ($arch:ty, $dim:tt, ($(<$scope:ty, $in:ty>),+$(,)?), $type:ty) => {
-        $(
-            unsafe_impl_projection_set!($arch, $dim, $scope, $in, $type);
-        )+
-    }
-}
-
-pub(crate) use unsafe_impl_projection_sets_inner;
-
 macro_rules! impl_projection {
     (<$space:ty, $in:ty> for $type:ty => {$arch:ty, $head:ty, $tail:ty}, {$dim:stmt}, {$idx:stmt}) => {
         impl Projection<$space, $in> for $type {
@@ -51,13 +18,13 @@ macro_rules! impl_projection {
             type Tail = $tail;
             #[inline]
             fn dim() -> <<Self::Arch as Arch>::IndexSize as SizeType>::Unsigned {
-                // SAFETY: Can only be called on nvptx architecture and these intrinsics
+                // SAFETY: Can only be called on the architecture of the projection and its intrinsics
                 // are not unsafe per se
                 $dim
             }
             #[inline]
             fn idx() -> <<Self::Arch as Arch>::IndexSize as SizeType>::Unsigned {
-                // SAFETY: Can only be called on nvptx architecture and these intrinsics
+                // SAFETY: Can only be called on the architecture of the projection and its intrinsics
                 // are not unsafe per se
                 $idx
             }
@@ -1,10 +1,24 @@
-use mdarray::{Dim, Shape};
+#[cfg(any(target_arch = "nvptx64", target_arch = "amdgpu"))]
+use core::sync::atomic::{AtomicU32, Ordering, fence};
+
+use mdarray::{Dim, Layout, Shape};
 use num_traits::{One, Zero};
 
-use crate::size_type::SizeType;
+use crate::{SimtArray, State, Uniform, size_type::SizeType};
 
 #[cfg(target_arch = "amdgpu")]
-mod amdgpu;
+pub mod amdgpu;
+#[cfg(all(
+    any(test, feature = "host"),
+    not(any(target_arch = "nvptx64", target_arch = "amdgpu"))
+))]
+pub mod host;
+#[cfg(any(
+    target_arch = "nvptx64",
+    target_arch = "amdgpu",
+    test,
+    feature = "host"
+))]
 mod macros;
 #[cfg(target_arch = "nvptx64")]
 pub mod nvptx;
@@ -30,11 +44,66 @@ pub trait Scope {
 pub trait SyncableScope: Scope {
     /// The function that must be used to synchronize all threads inside of this
     /// scope.
+    ///
+    /// # Safety
+    /// Must be called by all threads of the scope in a uniform control flow state
     unsafe fn sync();
 }
 
 pub trait UnitScope: Scope {}
 
+/// The grid of a cooperative launch, in which all blocks are resident at the
+/// same time so that it can be synchronized.
+///
+/// It has the same units as [`Grid`](Self::Grid), which cannot be synchronized,
+/// since a kernel does not know whether it was launched cooperatively. Arrays
+/// only become arrays of this scope through [`SimtArray::into_cooperative`],
+/// which requires a token that only cooperative kernels may create.
+///
+/// On the GPUs, synchronizing it uses a single arrival counter per module, the
+/// global [`GRID_BARRIER_SYMBOL`]. Every completed barrier leaves the counter
+/// valid for the next one, also in the next launch. Cooperative launches of one
+/// module must therefore not run at the same time, and the counter must be
+/// reset to zero before the next cooperative launch if a launch ended while
+/// blocks were waiting at a barrier, e.g. after it was aborted.
+pub trait CooperativeScope: SyncableScope {
+    /// The scope of the same units in any launch
+    type Grid: Scope<Arch = Self::Arch>;
+}
+
+impl<'s, T, Sc: Scope, I: State, L: Layout, Sh: Shape> SimtArray<'s, T, Sc, I, L, Sh> {
+    /// Turns an array of the grid into one of the cooperative grid, e.g. to
+    /// initialize it with `init_with` in one phase and read it in the next.
+    pub fn into_cooperative<C>(self, _uniform: &Uniform<C>) -> SimtArray<'s, T, C, I, L, Sh>
+    where
+        C: CooperativeScope<Grid = Sc>,
+    {
+        unsafe { SimtArray::new_unchecked(self.ptr, self.mapping) }
+    }
+}
+
+/// Name of the arrival counter of the grid barrier in the device code, so that
+/// the host can look it up, e.g. with `cuModuleGetGlobal`, to reset it. See
+/// [`CooperativeScope`].
+pub const GRID_BARRIER_SYMBOL: &str = "SIMTARRAY_GRID_BARRIER";
+
+/// Grid barrier for cooperative launches. Must be called by exactly one thread
+/// of every block after the block was synchronized.
+///
+/// The first block adds `0x8000_0000 - (blocks - 1)` to the counter and all
+/// other blocks add one, so the top bit flips once every block arrived. This
+/// keeps the counter valid for the next barrier without resetting it.
+#[cfg(any(target_arch = "nvptx64", target_arch = "amdgpu"))]
+#[inline]
+pub(crate) fn grid_barrier(counter: &AtomicU32, blocks: u32, first_block: bool) {
+    const FLIP: u32 = 0x8000_0000;
+    let inc = if first_block { FLIP - (blocks - 1) } else { 1 };
+    fence(Ordering::SeqCst);
+    let old = counter.fetch_add(inc, Ordering::AcqRel);
+    while (old ^ counter.load(Ordering::Acquire)) & FLIP == 0 {}
+    fence(Ordering::SeqCst);
+}
+
 pub trait Projection<S: Scope<Arch = Self::Arch>, O: Scope<Arch = Self::Arch>> {
     type Arch: Arch;
 
@@ -63,22 +132,99 @@ pub trait ProjectionSetDim0<Sh: Shape, S: Scope<Arch = Self::Arch>, O: Scope<Arc
     fn idx0() -> <<Self::Arch as Arch>::IndexSize as SizeType>::Unsigned;
 }
 
-impl<P0: Projection<S, O, Arch = Self::Arch>, D0: Dim, S, O> ProjectionSetDim0<(D0,), S, O> for (P0,)
-where
-    S: Scope<Arch = Self::Arch>,
-    O: Scope<Arch = Self::Arch>,
-    (P0,): ProjectionSet<(D0,), S, O>,
-{
-    fn dim0() -> <<Self::Arch as Arch>::IndexSize as SizeType>::Unsigned {
-        <P0 as Projection<S, O>>::dim()
-    }
-    fn idx0() -> <<Self::Arch as Arch>::IndexSize as SizeType>::Unsigned {
-        <P0 as Projection<S, O>>::idx()
-    }
+macro_rules! projection_set_dim {
+    ($(#[$attr:meta])* $name:ident: $super:ident, $dim:ident, $idx:ident) => {
+        $(#[$attr])*
+        pub trait $name<Sh: Shape, S: Scope<Arch = Self::Arch>, O: Scope<Arch = Self::Arch>>:
+            $super<Sh, S, O>
+        {
+            fn $dim() -> <<Self::Arch as Arch>::IndexSize as SizeType>::Unsigned;
+            fn $idx() -> <<Self::Arch as Arch>::IndexSize as SizeType>::Unsigned;
+        }
+    };
+}
+
+projection_set_dim!(ProjectionSetDim1: ProjectionSetDim0, dim1, idx1);
+projection_set_dim!(ProjectionSetDim2: ProjectionSetDim1, dim2, idx2);
+projection_set_dim!(
+    /// No arch implements [`ProjectionSet`] above rank 3 yet, so this trait
+    /// is not satisfied by any tuple of projections.
+    ProjectionSetDim3: ProjectionSetDim2, dim3, idx3
+);
+projection_set_dim!(
+    /// No arch implements [`ProjectionSet`] above rank 3 yet, so this trait
+    /// is not satisfied by any tuple of projections.
+    ProjectionSetDim4: ProjectionSetDim3, dim4, idx4
+);
+projection_set_dim!(
+    /// No arch implements [`ProjectionSet`] above rank 3 yet, so this trait
+    /// is not satisfied by any tuple of projections.
+    ProjectionSetDim5: ProjectionSetDim4, dim5, idx5
+);
+
+/// Implements `ProjectionSetDimK` for the tuple `($P, ..)` by forwarding
+/// `dimK`/`idxK` to the projection at position `K`.
+macro_rules! impl_projection_set_dim {
+    ($name:ident, $dim:ident, $idx:ident, $Pk:ident, ($($P:ident),+), ($($D:ident),+)) => {
+        impl<$($P,)+ $($D: Dim,)+ S, O> $name<($($D),+,), S, O> for ($($P),+,)
+        where
+            $($P: Projection<S, O, Arch = Self::Arch>,)+
+            S: Scope<Arch = Self::Arch>,
+            O: Scope<Arch = Self::Arch>,
+            ($($P),+,): ProjectionSet<($($D),+,), S, O>,
+        {
+            #[inline]
+            fn $dim() -> <<Self::Arch as Arch>::IndexSize as SizeType>::Unsigned {
+                <$Pk as Projection<S, O>>::dim()
+            }
+            #[inline]
+            fn $idx() -> <<Self::Arch as Arch>::IndexSize as SizeType>::Unsigned {
+                <$Pk as Projection<S, O>>::idx()
+            }
+        }
+    };
+}
+
+/// Implements `ProjectionSetDim0 ..= ProjectionSetDimK` for one tuple arity.
+macro_rules! impl_projection_set_dims {
+    ($Ps:tt, $Ds:tt, {$($name:ident, $dim:ident, $idx:ident, $Pk:ident);+$(;)?}) => {
+        $(impl_projection_set_dim!($name, $dim, $idx, $Pk, $Ps, $Ds);)+
+    };
 }
 
-// FIXME: add macro for ProjectionSetDimX implementation and implement it for
-// tuples (P0,) ..= (P0, P1, ..., P5) and maybe more
+impl_projection_set_dims!((P0), (D0), {
+    ProjectionSetDim0, dim0, idx0, P0;
+});
+impl_projection_set_dims!((P0, P1), (D0, D1), {
+    ProjectionSetDim0, dim0, idx0, P0;
+    ProjectionSetDim1, dim1, idx1, P1;
+});
+impl_projection_set_dims!((P0, P1, P2), (D0, D1, D2), {
+    ProjectionSetDim0, dim0, idx0, P0;
+    ProjectionSetDim1, dim1, idx1, P1;
+    ProjectionSetDim2, dim2, idx2, P2;
+});
+impl_projection_set_dims!((P0, P1, P2, P3), (D0, D1, D2, D3), {
+    ProjectionSetDim0, dim0, idx0, P0;
+    ProjectionSetDim1, dim1, idx1, P1;
+    ProjectionSetDim2, dim2, idx2, P2;
+    ProjectionSetDim3, dim3, idx3, P3;
+});
+impl_projection_set_dims!((P0, P1, P2, P3, P4), (D0, D1, D2, D3, D4), {
+    ProjectionSetDim0, dim0, idx0, P0;
+    ProjectionSetDim1, dim1, idx1, P1;
+    ProjectionSetDim2, dim2, idx2, P2;
+    ProjectionSetDim3, dim3, idx3, P3;
+    ProjectionSetDim4, dim4, idx4, P4;
+});
+impl_projection_set_dims!((P0, P1, P2, P3, P4, P5), (D0, D1, D2, D3, D4, D5), {
+    ProjectionSetDim0, dim0, idx0, P0;
+    ProjectionSetDim1, dim1, idx1, P1;
+    ProjectionSetDim2, dim2, idx2, P2;
+    ProjectionSetDim3, dim3, idx3, P3;
+    ProjectionSetDim4, dim4, idx4, P4;
+    ProjectionSetDim5, dim5, idx5, P5;
+});
 
 impl<A: Arch, S: Scope<Arch = A>, O: Scope<Arch = A>> Projection<S, O> for () {
     type Arch = A;
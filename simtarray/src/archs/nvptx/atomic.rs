@@ -0,0 +1,155 @@
+//! Atomics with the `atom` scope that matches the scope of the array:
+//! `cta` up to [`Block`], `cluster` for [`Cluster`] and `gpu` for [`Grid`] and
+//! [`CooperativeGrid`].
+use core::arch::asm;
+
+use crate::{AtomicElement, update};
+
+use super::{Block, Cluster, CooperativeGrid, Grid, Thread, Warp};
+
+/// Issues `atom.relaxed.<scope>.<op>.<type>` on a generic address and returns
+/// the previous value
+macro_rules! atom {
+    ($scope:literal, $op:literal, $ty:literal, $reg:ident, $ptr:expr, $a:expr) => {{
+        let old;
+        unsafe {
+            asm!(
+                concat!("atom.relaxed.", $scope, ".", $op, ".", $ty, " {}, [{}], {};"),
+                out($reg) old,
+                in(reg64) $ptr,
+                in($reg) $a,
+            )
+        };
+        old
+    }};
+    ($scope:literal, $op:literal, $ty:literal, $reg:ident, $ptr:expr, $a:expr, $b:expr) => {{
+        let old;
+        unsafe {
+            asm!(
+                concat!("atom.relaxed.", $scope, ".", $op, ".", $ty, " {}, [{}], {}, {};"),
+                out($reg) old,
+                in(reg64) $ptr,
+                in($reg) $a,
+                in($reg) $b,
+            )
+        };
+        old
+    }};
+}
+
+/// Issues `ld.relaxed.<scope>.<type>` on a generic address
+macro_rules! ld {
+    ($scope:literal, $ty:literal, $reg:ident, $ptr:expr) => {{
+        let value;
+        unsafe {
+            asm!(
+                concat!("ld.relaxed.", $scope, ".", $ty, " {}, [{}];"),
+                out($reg) value,
+                in(reg64) $ptr,
+            )
+        };
+        value
+    }};
+}
+
+/// Implements `AtomicElement` for integers, listed as
+/// `(type, register class, add type, min/max type, bit type)`
+macro_rules! impl_atomic_int {
+    (
+        $scope:literal,
+        $Sc:ty,
+        $(($T:ty, $reg:ident, $add:literal, $minmax:literal, $b:literal)),+
+    ) => {
+        $(
+            unsafe impl AtomicElement<$Sc> for $T {
+                #[inline]
+                unsafe fn load(ptr: *mut Self) -> Self {
+                    ld!($scope, $b, $reg, ptr)
+                }
+                #[inline]
+                unsafe fn fetch_add(ptr: *mut Self, value: Self) -> Self {
+                    atom!($scope, "add", $add, $reg, ptr, value)
+                }
+                #[inline]
+                unsafe fn fetch_min(ptr: *mut Self, value: Self) -> Self {
+                    atom!($scope, "min", $minmax, $reg, ptr, value)
+                }
+                #[inline]
+                unsafe fn fetch_max(ptr: *mut Self, value: Self) -> Self {
+                    atom!($scope, "max", $minmax, $reg, ptr, value)
+                }
+                #[inline]
+                unsafe fn compare_exchange(
+                    ptr: *mut Self,
+                    current: Self,
+                    new: Self,
+                ) -> Result<Self, Self> {
+                    let old: Self = atom!($scope, "cas", $b, $reg, ptr, current, new);
+                    if old == current { Ok(old) } else { Err(old) }
+                }
+            }
+        )+
+    };
+}
+
+/// Implements `AtomicElement` for floats, listed as
+/// `(type, register class, add type, bit type, integer of the same size)`. There is no
+/// float `atom.min` before sm_90, so min and max loop on compare exchange.
+macro_rules! impl_atomic_float {
+    ($scope:literal, $Sc:ty, $(($T:ty, $reg:ident, $add:literal, $b:literal, $U:ty)),+) => {
+        $(
+            unsafe impl AtomicElement<$Sc> for $T {
+                #[inline]
+                unsafe fn load(ptr: *mut Self) -> Self {
+                    ld!($scope, $b, $reg, ptr)
+                }
+                #[inline]
+                unsafe fn fetch_add(ptr: *mut Self, value: Self) -> Self {
+                    atom!($scope, "add", $add, $reg, ptr, value)
+                }
+                #[inline]
+                unsafe fn fetch_min(ptr: *mut Self, value: Self) -> Self {
+                    unsafe { update::<Self, $Sc>(ptr, |old| (value < old).then_some(value)) }
+                }
+                #[inline]
+                unsafe fn fetch_max(ptr: *mut Self, value: Self) -> Self {
+                    unsafe { update::<Self, $Sc>(ptr, |old| (value > old).then_some(value)) }
+                }
+                #[inline]
+                unsafe fn compare_exchange(
+                    ptr: *mut Self,
+                    current: Self,
+                    new: Self,
+                ) -> Result<Self, Self> {
+                    let (ptr, current, new) = (ptr.cast(), current.to_bits(), new.to_bits());
+                    unsafe { <$U as AtomicElement<$Sc>>::compare_exchange(ptr, current, new) }
+                        .map(<$T>::from_bits)
+                        .map_err(<$T>::from_bits)
+                }
+            }
+        )+
+    };
+}
+
+macro_rules! impl_atomics {
+    ($($scope:literal => $Sc:ty),+) => {
+        $(
+            impl_atomic_int!(
+                $scope,
+                $Sc,
+                (u32, reg32, "u32", "u32", "b32"),
+                (i32, reg32, "s32", "s32", "b32"),
+                (u64, reg64, "u64", "u64", "b64"),
+                (i64, reg64, "u64", "s64", "b64")
+            );
+            impl_atomic_float!(
+                $scope,
+                $Sc,
+                (f32, reg32, "f32", "b32", u32),
+                (f64, reg64, "f64", "b64", u64)
+            );
+        )+
+    };
+}
+
+impl_atomics!("cta" => Thread, "cta" => Warp, "cta" => Block, "cluster" => Cluster, "gpu" => Grid, "gpu" => CooperativeGrid);
@@ -0,0 +1,92 @@
+//! `cp.async` copies from global into shared memory (sm_80+). Elements are
+//! copied in the largest chunks their alignment allows, 16 byte chunks bypass
+//! the L1 cache with `.cg`, smaller ones go through it with `.ca`. Elements
+//! that are not copied from global into shared memory are copied
+//! synchronously.
+use core::{
+    arch::asm,
+    mem::{align_of, size_of},
+};
+
+use crate::AsyncCopyScope;
+
+use super::Block;
+
+/// Issues `cp.async.<cache>.shared.global` for one chunk of `$size` bytes
+/// between generic addresses
+macro_rules! cp_async {
+    ($cache:literal, $size:literal, $dst:expr, $src:expr) => {
+        asm!(
+            "{{",
+            ".reg .u64 s, g;",
+            "cvta.to.shared.u64 s, {dst};",
+            "cvta.to.global.u64 g, {src};",
+            concat!("cp.async.", $cache, ".shared.global [s], [g], ", $size, ";"),
+            "}}",
+            dst = in(reg64) $dst,
+            src = in(reg64) $src,
+        )
+    };
+}
+
+/// Whether the generic address `$ptr` points into the state space `$space`
+macro_rules! isspacep {
+    ($space:literal, $ptr:expr) => {{
+        let is: u32;
+        unsafe {
+            asm!(
+                "{{",
+                ".reg .pred p;",
+                concat!("isspacep.", $space, " p, {ptr};"),
+                "selp.u32 {is}, 1, 0, p;",
+                "}}",
+                ptr = in(reg64) $ptr,
+                is = out(reg32) is,
+            )
+        };
+        is != 0
+    }};
+}
+
+/// Largest chunk size `cp.async` supports for `T`
+const fn chunk<T>() -> usize {
+    assert!(
+        size_of::<T>().is_multiple_of(4) && align_of::<T>() >= 4,
+        "cp.async copies elements in chunks of 4, 8 or 16 aligned bytes"
+    );
+    let mut chunk = 16;
+    while !(align_of::<T>() >= chunk && size_of::<T>().is_multiple_of(chunk)) {
+        chunk /= 2;
+    }
+    chunk
+}
+
+unsafe impl AsyncCopyScope for Block {
+    #[inline]
+    unsafe fn copy<T: Copy>(dst: *mut T, src: *const T) {
+        if !isspacep!("global", src) || !isspacep!("shared", dst) {
+            unsafe { dst.write(src.read()) };
+            return;
+        }
+        let chunk = const { chunk::<T>() };
+        let (dst, src) = (dst.cast::<u8>(), src.cast::<u8>());
+        for offset in (0..size_of::<T>()).step_by(chunk) {
+            let (dst, src) = unsafe { (dst.add(offset), src.add(offset)) };
+            unsafe {
+                match chunk {
+                    16 => cp_async!("cg", 16, dst, src),
+                    8 => cp_async!("ca", 8, dst, src),
+                    _ => cp_async!("ca", 4, dst, src),
+                }
+            }
+        }
+    }
+    #[inline]
+    unsafe fn commit() {
+        unsafe { asm!("cp.async.commit_group;") }
+    }
+    #[inline]
+    fn wait_group<const N: u32>() {
+        unsafe { asm!("cp.async.wait_group {};", const N) }
+    }
+}
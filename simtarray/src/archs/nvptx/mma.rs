@@ -0,0 +1,351 @@
+//! Warp wide matrix multiply-accumulate on tensor cores (`mma.sync`, sm_80+).
+//!
+//! A warp multiplies an `M x K` fragment of `A` with a `K x N` fragment of `B`
+//! and adds the product to an `M x N` accumulator. Every lane holds a fixed
+//! part of each fragment, fragments are loaded from and stored to 2D arrays
+//! through their mappings, so any layout of the tiles works.
+use core::{arch::asm, marker::PhantomData};
+
+use mdarray::{Dim, Layout, Mapping};
+
+use crate::{Init, Scope, SimtArray, SyncableScope, Viewable};
+
+pub use crate::Bf16;
+
+use super::{Warp, lane_id};
+
+/// Input element types of `mma.sync` together with the instruction shape they
+/// are used with. `f32` operands are multiplied as tf32.
+///
+/// # Safety
+/// The positions must be the fragment layouts of the PTX ISA for the
+/// instruction that `mma` issues, which must compute `a * b + c`.
+pub unsafe trait MmaElement: Copy {
+    /// Element type of the accumulator
+    type Acc: Copy + Default;
+    const M: usize = 16;
+    const N: usize = 8;
+    const K: usize;
+    /// Elements packed into each 32 bit register of the `A` and `B` fragments
+    const PER_REG: usize;
+
+    /// The bits of the element in the low bits of the register
+    fn to_bits(self) -> u32;
+    /// Row and column of the `i`th element of `A` held by a lane, given by
+    /// its group (`lane / 4`) and its index inside of the group (`lane % 4`)
+    fn a_pos(group: usize, thread: usize, i: usize) -> (usize, usize);
+    /// Row and column of the `i`th element of `B`, see [`a_pos`](Self::a_pos)
+    fn b_pos(group: usize, thread: usize, i: usize) -> (usize, usize);
+    /// # Safety
+    /// Must be called by all lanes of the warp in uniform control flow.
+    unsafe fn mma(a: &[u32; 4], b: &[u32; 2], c: &mut [Self::Acc; 4]);
+}
+
+/// Layout of 16 bit operands of `m16n8k16`
+fn a_pos_16bit(group: usize, thread: usize, i: usize) -> (usize, usize) {
+    (
+        group + 8 * ((i >> 1) & 1),
+        thread * 2 + (i & 1) + 8 * (i >> 2),
+    )
+}
+
+fn b_pos_16bit(group: usize, thread: usize, i: usize) -> (usize, usize) {
+    (thread * 2 + (i & 1) + 8 * (i >> 1), group)
+}
+
+/// Layout of the accumulators, shared by all shapes
+fn c_pos(group: usize, thread: usize, i: usize) -> (usize, usize) {
+    (group + 8 * (i >> 1), thread * 2 + (i & 1))
+}
+
+/// Issues `mma.sync` with the accumulator as input and output
+macro_rules! mma {
+    ($shape:literal, $types:literal, $a:expr, $b:expr, $c:expr) => {
+        asm!(
+            concat!(
+                "mma.sync.aligned.", $shape, ".row.col.", $types,
+                " {{{c0}, {c1}, {c2}, {c3}}}, {{{a0}, {a1}, {a2}, {a3}}}, {{{b0}, {b1}}},",
+                " {{{c0}, {c1}, {c2}, {c3}}};"
+            ),
+            a0 = in(reg32) $a[0],
+            a1 = in(reg32) $a[1],
+            a2 = in(reg32) $a[2],
+            a3 = in(reg32) $a[3],
+            b0 = in(reg32) $b[0],
+            b1 = in(reg32) $b[1],
+            c0 = inout(reg32) $c[0],
+            c1 = inout(reg32) $c[1],
+            c2 = inout(reg32) $c[2],
+            c3 = inout(reg32) $c[3],
+        )
+    };
+}
+
+unsafe impl MmaElement for f16 {
+    type Acc = f32;
+    const K: usize = 16;
+    const PER_REG: usize = 2;
+
+    fn to_bits(self) -> u32 {
+        f16::to_bits(self) as u32
+    }
+    fn a_pos(group: usize, thread: usize, i: usize) -> (usize, usize) {
+        a_pos_16bit(group, thread, i)
+    }
+    fn b_pos(group: usize, thread: usize, i: usize) -> (usize, usize) {
+        b_pos_16bit(group, thread, i)
+    }
+    #[inline]
+    unsafe fn mma(a: &[u32; 4], b: &[u32; 2], c: &mut [f32; 4]) {
+        unsafe { mma!("m16n8k16", "f32.f16.f16.f32", a, b, c) }
+    }
+}
+
+unsafe impl MmaElement for Bf16 {
+    type Acc = f32;
+    const K: usize = 16;
+    const PER_REG: usize = 2;
+
+    fn to_bits(self) -> u32 {
+        self.0 as u32
+    }
+    fn a_pos(group: usize, thread: usize, i: usize) -> (usize, usize) {
+        a_pos_16bit(group, thread, i)
+    }
+    fn b_pos(group: usize, thread: usize, i: usize) -> (usize, usize) {
+        b_pos_16bit(group, thread, i)
+    }
+    #[inline]
+    unsafe fn mma(a: &[u32; 4], b: &[u32; 2], c: &mut [f32; 4]) {
+        unsafe { mma!("m16n8k16", "f32.bf16.bf16.f32", a, b, c) }
+    }
+}
+
+/// The low 13 mantissa bits are ignored by the tensor cores.
+unsafe impl MmaElement for f32 {
+    type Acc = f32;
+    const K: usize = 8;
+    const PER_REG: usize = 1;
+
+    fn to_bits(self) -> u32 {
+        f32::to_bits(self)
+    }
+    fn a_pos(group: usize, thread: usize, i: usize) -> (usize, usize) {
+        (group + 8 * (i & 1), thread + 4 * (i >> 1))
+    }
+    fn b_pos(group: usize, thread: usize, i: usize) -> (usize, usize) {
+        (thread + 4 * i, group)
+    }
+    #[inline]
+    unsafe fn mma(a: &[u32; 4], b: &[u32; 2], c: &mut [f32; 4]) {
+        unsafe { mma!("m16n8k8", "f32.tf32.tf32.f32", a, b, c) }
+    }
+}
+
+/// Products are accumulated in `i32` without saturation.
+unsafe impl MmaElement for i8 {
+    type Acc = i32;
+    const K: usize = 32;
+    const PER_REG: usize = 4;
+
+    fn to_bits(self) -> u32 {
+        self as u8 as u32
+    }
+    fn a_pos(group: usize, thread: usize, i: usize) -> (usize, usize) {
+        (
+            group + 8 * ((i >> 2) & 1),
+            thread * 4 + (i & 3) + 16 * (i >> 3),
+        )
+    }
+    fn b_pos(group: usize, thread: usize, i: usize) -> (usize, usize) {
+        (thread * 4 + (i & 3) + 16 * (i >> 2), group)
+    }
+    #[inline]
+    unsafe fn mma(a: &[u32; 4], b: &[u32; 2], c: &mut [i32; 4]) {
+        unsafe { mma!("m16n8k32", "s32.s8.s8.s32", a, b, c) }
+    }
+}
+
+/// Group of the calling lane and its index inside of the group
+#[inline]
+fn lane_pos() -> (usize, usize) {
+    let lane = lane_id() as usize;
+    (lane / 4, lane % 4)
+}
+
+/// Pointer to the element at `(row, col)` of a 2D array
+///
+/// # Safety
+/// The position must be inside of the array.
+#[inline]
+unsafe fn element<T, M: Mapping>(ptr: *mut T, mapping: &M, row: usize, col: usize) -> *mut T {
+    unsafe { ptr.offset(row as isize * mapping.stride(0) + col as isize * mapping.stride(1)) }
+}
+
+/// Packs the elements of a fragment at the positions `pos` relative to
+/// `(row, col)` into registers
+#[inline]
+fn load<T, Sc, I, L, D0, D1, const R: usize>(
+    tile: &SimtArray<'_, T, Sc, I, L, (D0, D1)>,
+    (row, col): (usize, usize),
+    (rows, cols): (usize, usize),
+    pos: fn(usize, usize, usize) -> (usize, usize),
+) -> [u32; R]
+where
+    T: MmaElement,
+    Sc: Scope,
+    I: Viewable,
+    L: Layout,
+    D0: Dim,
+    D1: Dim,
+{
+    assert!(
+        row + rows <= tile.mapping.dim(0) && col + cols <= tile.mapping.dim(1),
+        "fragment exceeds the tile"
+    );
+    let (group, thread) = lane_pos();
+    let mut regs = [0; R];
+    for i in 0..R * T::PER_REG {
+        let (r, c) = pos(group, thread, i);
+        let value = unsafe { element(tile.ptr, &tile.mapping, row + r, col + c).read() };
+        let shift = (i % T::PER_REG) * (32 / T::PER_REG);
+        regs[i / T::PER_REG] |= value.to_bits() << shift;
+    }
+    regs
+}
+
+/// The `M x K` operand `A` of a warp, row major in the sense of `mma.sync`.
+pub struct FragmentA<T> {
+    regs: [u32; 4],
+    element: PhantomData<T>,
+}
+
+impl<T: MmaElement> FragmentA<T> {
+    /// Loads the `M x K` block of `tile` that starts at `(row, col)`. Every lane
+    /// reads its own elements.
+    ///
+    /// # Panics
+    /// Panics if the block exceeds the tile.
+    pub fn load_from<Sc, I, L, D0, D1>(
+        tile: &SimtArray<'_, T, Sc, I, L, (D0, D1)>,
+        (row, col): (usize, usize),
+    ) -> Self
+    where
+        Sc: Scope,
+        I: Viewable,
+        L: Layout,
+        D0: Dim,
+        D1: Dim,
+    {
+        Self {
+            regs: load(tile, (row, col), (T::M, T::K), T::a_pos),
+            element: PhantomData,
+        }
+    }
+}
+
+/// The `K x N` operand `B` of a warp, column major in the sense of `mma.sync`.
+pub struct FragmentB<T> {
+    regs: [u32; 2],
+    element: PhantomData<T>,
+}
+
+impl<T: MmaElement> FragmentB<T> {
+    /// Loads the `K x N` block of `tile` that starts at `(row, col)`, see
+    /// [`FragmentA::load_from`].
+    pub fn load_from<Sc, I, L, D0, D1>(
+        tile: &SimtArray<'_, T, Sc, I, L, (D0, D1)>,
+        (row, col): (usize, usize),
+    ) -> Self
+    where
+        Sc: Scope,
+        I: Viewable,
+        L: Layout,
+        D0: Dim,
+        D1: Dim,
+    {
+        Self {
+            regs: load(tile, (row, col), (T::K, T::N), T::b_pos),
+            element: PhantomData,
+        }
+    }
+}
+
+/// The `M x N` accumulator of a warp for inputs `T`.
+pub struct Accumulator<T: MmaElement> {
+    values: [T::Acc; 4],
+}
+
+impl<T: MmaElement> Accumulator<T> {
+    pub fn zero() -> Self {
+        Self {
+            values: [T::Acc::default(); 4],
+        }
+    }
+
+    /// Loads the `M x N` block of `tile` that starts at `(row, col)`, see
+    /// [`FragmentA::load_from`].
+    pub fn load_from<Sc, I, L, D0, D1>(
+        tile: &SimtArray<'_, T::Acc, Sc, I, L, (D0, D1)>,
+        (row, col): (usize, usize),
+    ) -> Self
+    where
+        Sc: Scope,
+        I: Viewable,
+        L: Layout,
+        D0: Dim,
+        D1: Dim,
+    {
+        assert!(
+            row + T::M <= tile.mapping.dim(0) && col + T::N <= tile.mapping.dim(1),
+            "fragment exceeds the tile"
+        );
+        let (group, thread) = lane_pos();
+        let values = core::array::from_fn(|i| {
+            let (r, c) = c_pos(group, thread, i);
+            unsafe { element(tile.ptr, &tile.mapping, row + r, col + c).read() }
+        });
+        Self { values }
+    }
+
+    /// Adds `a * b` to the accumulator.
+    ///
+    /// # Safety
+    /// Must be called in a kernel uniform control flow state
+    pub unsafe fn mma(&mut self, a: &FragmentA<T>, b: &FragmentB<T>) {
+        unsafe { T::mma(&a.regs, &b.regs, &mut self.values) }
+    }
+
+    /// Stores the accumulator into the `M x N` block of `tile` that starts at
+    /// `(row, col)` and synchronizes the warp. The tile can be of a larger scope,
+    /// e.g. a `Block` tile of which every warp stores its own block.
+    ///
+    /// # Panics
+    /// Panics if the block exceeds the tile.
+    ///
+    /// # Safety
+    /// Must be called in a kernel uniform control flow state. The blocks the
+    /// warps of the scope of `tile` store to must be disjoint, and the other
+    /// warps may only read them after the scope was synchronized.
+    pub unsafe fn store_to<Sc, L, D0, D1>(
+        &self,
+        tile: &mut SimtArray<'_, T::Acc, Sc, Init, L, (D0, D1)>,
+        (row, col): (usize, usize),
+    ) where
+        Sc: Scope,
+        L: Layout,
+        D0: Dim,
+        D1: Dim,
+    {
+        assert!(
+            row + T::M <= tile.mapping.dim(0) && col + T::N <= tile.mapping.dim(1),
+            "fragment exceeds the tile"
+        );
+        let (group, thread) = lane_pos();
+        for (i, &value) in self.values.iter().enumerate() {
+            let (r, c) = c_pos(group, thread, i);
+            unsafe { element(tile.ptr, &tile.mapping, row + r, col + c).write(value) };
+        }
+        unsafe { Warp::sync() };
+    }
+}
@@ -0,0 +1,378 @@
+use mdarray::{Dense, DenseMapping, Dim, Layout};
+
+use crate::{SharedMemory, SimtArray, State, Uninit, size_type::_32Bit};
+
+use super::macros::*;
+use super::*;
+use core::arch::{asm, nvptx::*};
+use simtarray_derive::unsafe_impl_projection_sets;
+
+mod atomic;
+mod copy_async;
+pub mod mma;
+pub mod tma;
+pub mod warp;
+
+pub struct Nvptx;
+
+impl Arch for Nvptx {
+    type Scope<S: Scope<Arch = Self>> = S;
+
+    type IndexSize = _32Bit;
+}
+
+pub struct Grid;
+/// The grid of a cooperative launch, in which all blocks are resident at the
+/// same time so that it can be synchronized. Only available in kernels launched
+/// cooperatively, see [`SimtArray::into_cooperative`](crate::SimtArray::into_cooperative).
+pub struct CooperativeGrid;
+/// Thread block cluster (sm_90). Only available in kernels launched with a
+/// cluster dimension.
+pub struct Cluster;
+pub struct Block;
+pub struct Warp;
+pub struct Thread;
+
+impl_scope!(Nvptx, (Grid, CooperativeGrid, Cluster, Block, Warp, Thread));
+
+impl UnitScope for Thread {}
+
+impl SyncableScope for Block {
+    #[inline]
+    unsafe fn sync() {
+        unsafe { _syncthreads() }
+    }
+}
+
+/// Arrival counter of the grid barrier. Lives in global memory and is zero
+/// initialized when the module is loaded. It is shared by all cooperative
+/// launches of the module, see [`CooperativeScope`] for when it must be reset.
+#[unsafe(export_name = "SIMTARRAY_GRID_BARRIER")]
+static GRID_BARRIER: core::sync::atomic::AtomicU32 = core::sync::atomic::AtomicU32::new(0);
+
+impl SyncableScope for CooperativeGrid {
+    #[inline]
+    unsafe fn sync() {
+        unsafe {
+            _syncthreads();
+            if linear_thread_idx() == 0 {
+                let blocks = _grid_dim_x() * _grid_dim_y() * _grid_dim_z();
+                let first_block = _block_idx_x() == 0 && _block_idx_y() == 0 && _block_idx_z() == 0;
+                grid_barrier(&GRID_BARRIER, blocks, first_block);
+            }
+            _syncthreads();
+        }
+    }
+}
+
+impl CooperativeScope for CooperativeGrid {
+    type Grid = Grid;
+}
+
+impl SyncableScope for Cluster {
+    #[inline]
+    unsafe fn sync() {
+        unsafe { asm!("barrier.cluster.arrive;", "barrier.cluster.wait;") }
+    }
+}
+
+impl SyncableScope for Warp {
+    #[inline]
+    unsafe fn sync() {
+        // A partial last warp has no lanes for the full mask
+        unsafe { asm!("bar.warp.sync {};", in(reg32) warp::LaneMask::active().bits()) }
+    }
+}
+
+pub const WARP_SIZE: u32 = 32;
+
+/// Reads a 32 bit special register that has no intrinsic
+macro_rules! sreg {
+    ($reg:literal) => {{
+        let value: u32;
+        unsafe { asm!(concat!("mov.u32 {}, ", $reg, ";"), out(reg32) value) };
+        value
+    }};
+}
+
+#[inline]
+fn lane_id() -> u32 {
+    sreg!("%laneid")
+}
+
+/// Linear index of the thread inside of its block. Warps are formed from
+/// consecutive linear indices.
+#[inline]
+fn linear_thread_idx() -> u32 {
+    unsafe {
+        _thread_idx_x() + _block_dim_x() * (_thread_idx_y() + _block_dim_y() * _thread_idx_z())
+    }
+}
+
+#[inline]
+fn block_threads() -> u32 {
+    unsafe { _block_dim_x() * _block_dim_y() * _block_dim_z() }
+}
+
+#[inline]
+fn warps_per_block() -> u32 {
+    block_threads().div_ceil(WARP_SIZE)
+}
+
+/// Number of lanes of the calling thread's warp. Only the last warp of a block
+/// can have less than [`WARP_SIZE`].
+#[inline]
+fn warp_lanes() -> u32 {
+    WARP_SIZE.min(block_threads() - linear_thread_idx() / WARP_SIZE * WARP_SIZE)
+}
+
+pub struct X;
+pub struct Y;
+pub struct Z;
+pub struct Xy;
+pub struct Xz;
+pub struct Xyz;
+pub struct Yz;
+
+impl_projection!(
+    <Thread, Block> for X => { Nvptx, X, () },
+    { unsafe { _block_dim_x() } },
+    { unsafe { _thread_idx_x() } }
+);
+impl_projection!(
+    <Thread, Block> for Y => { Nvptx, Y, () },
+    { unsafe { _block_dim_y() } },
+    { unsafe { _thread_idx_y() } }
+);
+impl_projection!(
+    <Thread, Block> for Z => { Nvptx, Z, () },
+    { unsafe { _block_dim_z() } },
+    { unsafe { _thread_idx_z() } }
+);
+
+/// Implements the projections into a grid, which are the same for a
+/// cooperative one.
+macro_rules! impl_grid_projections {
+    ($($grid:ty),+) => {
+        $(
+            impl_projection!(
+                <Thread, $grid> for X => { Nvptx, X, () },
+                { unsafe { _block_dim_x() * _grid_dim_x() } },
+                { unsafe { _block_dim_x() * _block_idx_x() + _thread_idx_x() } }
+            );
+            impl_projection!(
+                <Thread, $grid> for Y => { Nvptx, Y, () },
+                { unsafe { _block_dim_y() * _grid_dim_y() } },
+                { unsafe { _block_dim_y() * _block_idx_y() + _thread_idx_y() } }
+            );
+            impl_projection!(
+                <Thread, $grid> for Z => { Nvptx, Z, () },
+                { unsafe { _block_dim_z() * _grid_dim_z() } },
+                { unsafe { _block_dim_z() * _block_idx_z() + _thread_idx_z() } }
+            );
+            impl_projection!(
+                <Block, $grid> for X => { Nvptx, X, () },
+                { unsafe { _grid_dim_x() } },
+                { unsafe { _block_idx_x() } }
+            );
+            impl_projection!(
+                <Block, $grid> for Y => { Nvptx, Y, () },
+                { unsafe { _grid_dim_y() } },
+                { unsafe { _block_idx_y() } }
+            );
+            impl_projection!(
+                <Block, $grid> for Z => { Nvptx, Z, () },
+                { unsafe { _grid_dim_z() } },
+                { unsafe { _block_idx_z() } }
+            );
+            impl_projection!(
+                <Cluster, $grid> for X => { Nvptx, X, () },
+                { sreg!("%nclusterid.x") },
+                { sreg!("%clusterid.x") }
+            );
+            impl_projection!(
+                <Cluster, $grid> for Y => { Nvptx, Y, () },
+                { sreg!("%nclusterid.y") },
+                { sreg!("%clusterid.y") }
+            );
+            impl_projection!(
+                <Cluster, $grid> for Z => { Nvptx, Z, () },
+                { sreg!("%nclusterid.z") },
+                { sreg!("%clusterid.z") }
+            );
+            impl_projections!(<Thread,$grid> for Xy, Xz, Yz, Xyz => {
+                Nvptx, (X, X, Y, X), (Y, Z, Z, Yz)
+            });
+            impl_projections!(<Block,$grid> for Xy, Xz, Yz, Xyz => {
+                Nvptx, (X, X, Y, X), (Y, Z, Z, Yz)
+            });
+            impl_projections!(<Cluster,$grid> for Xy, Xz, Yz, Xyz => {
+                Nvptx, (X, X, Y, X), (Y, Z, Z, Yz)
+            });
+        )+
+    };
+}
+
+impl_grid_projections!(Grid, CooperativeGrid);
+
+impl_projection!(
+    <Block, Cluster> for X => { Nvptx, X, () },
+    { sreg!("%cluster_nctaid.x") },
+    { sreg!("%cluster_ctaid.x") }
+);
+impl_projection!(
+    <Block, Cluster> for Y => { Nvptx, Y, () },
+    { sreg!("%cluster_nctaid.y") },
+    { sreg!("%cluster_ctaid.y") }
+);
+impl_projection!(
+    <Block, Cluster> for Z => { Nvptx, Z, () },
+    { sreg!("%cluster_nctaid.z") },
+    { sreg!("%cluster_ctaid.z") }
+);
+impl_projection!(
+    <Thread, Cluster> for X => { Nvptx, X, () },
+    { sreg!("%cluster_nctaid.x") * unsafe { _block_dim_x() } },
+    { sreg!("%cluster_ctaid.x") * unsafe { _block_dim_x() } + unsafe { _thread_idx_x() } }
+);
+impl_projection!(
+    <Thread, Cluster> for Y => { Nvptx, Y, () },
+    { sreg!("%cluster_nctaid.y") * unsafe { _block_dim_y() } },
+    { sreg!("%cluster_ctaid.y") * unsafe { _block_dim_y() } + unsafe { _thread_idx_y() } }
+);
+impl_projection!(
+    <Thread, Cluster> for Z => { Nvptx, Z, () },
+    { sreg!("%cluster_nctaid.z") * unsafe { _block_dim_z() } },
+    { sreg!("%cluster_ctaid.z") * unsafe { _block_dim_z() } + unsafe { _thread_idx_z() } }
+);
+// Warps are one dimensional, so only the linearized Xyz projection exists
+impl_projection!(
+    <Thread, Warp> for Xyz => { Nvptx, Xyz, () },
+    { warp_lanes() },
+    { lane_id() }
+);
+impl_projection!(
+    <Warp, Block> for Xyz => { Nvptx, Xyz, () },
+    { warps_per_block() },
+    { linear_thread_idx() / WARP_SIZE }
+);
+impl_projections!(<Thread,Block> for Xy, Xz, Yz, Xyz => {Nvptx, (X, X, Y, X), (Y, Z, Z, Yz)});
+impl_projections!(<Block,Cluster> for Xy, Xz, Yz, Xyz => {Nvptx, (X, X, Y, X), (Y, Z, Z, Yz)});
+impl_projections!(<Thread,Cluster> for Xy, Xz, Yz, Xyz => {Nvptx, (X, X, Y, X), (Y, Z, Z, Yz)});
+
+unsafe_impl_projection_sets!(Nvptx, [X, Y, Z], 3, (
+    <Thread, Grid>,
+    <Thread, Cluster>,
+    <Thread, Block>,
+    <Block, Grid>,
+    <Block, Cluster>,
+    <Cluster, Grid>,
+    <Thread, CooperativeGrid>,
+    <Block, CooperativeGrid>,
+    <Cluster, CooperativeGrid>,
+));
+unsafe_impl_projection_sets!(Nvptx, [Xyz], 3, (<Thread, Warp>, <Warp, Block>));
+
+impl<'s, T, I: State, L: Layout, Sh: Shape> SimtArray<'s, T, Cluster, I, L, Sh> {
+    /// Creates a cluster scoped array over the distributed shared memory of the
+    /// block with the given `rank` inside of the cluster. All blocks of the
+    /// cluster access the same elements.
+    ///
+    /// # Safety
+    /// `ptr` must point to a shared memory buffer that holds the array described
+    /// by `mapping` and that is located at the same address in every block of
+    /// the cluster. `rank` must be smaller than the number of blocks in the
+    /// cluster and the block with this rank must not exit before the other
+    /// blocks stopped accessing its shared memory.
+    pub unsafe fn from_distributed_shared(ptr: *mut T, mapping: L::Mapping<Sh>, rank: u32) -> Self {
+        let mapped: *mut T;
+        unsafe {
+            asm!("mapa.u64 {}, {}, {};", out(reg64) mapped, in(reg64) ptr, in(reg32) rank);
+            Self::new_unchecked(mapped, mapping)
+        }
+    }
+}
+
+/// Upper bound of statically allocated shared memory per block in bytes.
+pub const MAX_STATIC_SHARED_BYTES: usize = 48 * 1024;
+
+/// Declares a statically sized shared memory buffer and returns a block scoped
+/// `SimtArray<'s, T, Block, Uninit, Dense, (Const<N0>, ..)>` over it, where `'s`
+/// is the borrow of the given [`&SharedMemory`](crate::SharedMemory).
+///
+/// ```ignore
+/// let tile = shared_array!(shared; f32; 32, 33);
+/// ```
+///
+/// The buffer is sized from the extents, so the shape always fits. Exceeding
+/// [`MAX_STATIC_SHARED_BYTES`] or empty extents fail to compile.
+///
+/// An alignment beyond that of `T` can be requested, e.g. for the tiles of bulk
+/// tensor copies:
+///
+/// ```ignore
+/// let tile = shared_array!(shared; f32; 64, 64; align = 128);
+/// ```
+///
+/// The calling crate needs `asm_experimental_arch`.
+///
+/// # Panics
+/// Every expansion site returns its buffer only once per kernel. Evaluating it
+/// again with the same token, e.g. in a loop, panics instead of returning a
+/// second array over the same memory.
+#[macro_export]
+macro_rules! shared_array {
+    ($shared:expr; $T:ty; $($N:expr),+ $(,)?) => {
+        $crate::shared_array!($shared; $T; $($N),+; align = ::core::mem::align_of::<$T>())
+    };
+    ($shared:expr; $T:ty; $($N:expr),+; align = $align:expr) => {{
+        type Shape = ($($crate::mdarray::Const<{ $N }>,)+);
+        const LEN: usize = 1 $(* $N)+;
+        const SIZE: usize = LEN * ::core::mem::size_of::<$T>();
+        const ALIGN: usize = $align;
+        const {
+            assert!(LEN > 0, "shared arrays must not be empty");
+            assert!(
+                SIZE <= $crate::nvptx::MAX_STATIC_SHARED_BYTES,
+                "shared array exceeds the static shared memory of a block"
+            );
+            assert!(
+                ALIGN.is_power_of_two() && ALIGN >= ::core::mem::align_of::<$T>(),
+                "alignment must be a power of two of at least that of the element type"
+            );
+        }
+        let shared: &$crate::SharedMemory = $shared;
+        let ptr: *mut $T;
+        // SAFETY: Only declares the buffer and takes its address
+        unsafe {
+            ::core::arch::asm!(
+                "{{",
+                ".shared .align {align} .b8 smem[{size}];",
+                "cvta.shared.u64 {ptr}, smem;",
+                "}}",
+                align = const ALIGN,
+                size = const SIZE,
+                ptr = out(reg64) ptr,
+            );
+        }
+        // SAFETY: `ptr` is the buffer of this site, which holds `Shape`
+        unsafe { $crate::nvptx::shared_array_at::<$T, Shape>(ptr, shared) }
+    }};
+}
+
+/// The array of a [`shared_array!`] buffer. Shared memory lives as long as the
+/// kernel, so it may be borrowed as long as the token. The address of the
+/// buffer identifies the site, so every buffer is only handed out once.
+///
+/// # Safety
+/// `ptr` must point to the buffer of an expansion site of [`shared_array!`]
+/// that is large enough for `Sh`.
+#[doc(hidden)]
+#[inline]
+pub unsafe fn shared_array_at<'s, T, Sh: Shape + Default>(
+    ptr: *mut T,
+    shared: &'s SharedMemory,
+) -> SimtArray<'s, T, Block, Uninit, Dense, Sh> {
+    shared.take(ptr as usize);
+    unsafe { SimtArray::new_unchecked(ptr, DenseMapping::new(Sh::default())) }
+}
@@ -0,0 +1,347 @@
+//! Bulk tensor copies of the Tensor Memory Accelerator (sm_90) between `Grid`
+//! arrays in global memory and `Block` tiles in shared memory.
+//!
+//! A load is issued by a single thread and completes on an [`MBarrier`] that
+//! every thread of the block arrives at. A store is issued by a single thread
+//! after the block wrote the tile and completes through a bulk async group.
+use core::{
+    arch::asm,
+    marker::PhantomData,
+    mem::{ManuallyDrop, size_of},
+};
+
+use mdarray::{Const, Dense, Layout, Mapping, Shape};
+
+use crate::{Init, SimtArray, SyncableScope, Uninit, Viewable};
+
+use super::{Block, Grid, block_threads, linear_thread_idx};
+
+/// Shared memory tiles of bulk tensor copies must be aligned to 128 bytes, see
+/// the `align` option of [`shared_array!`](crate::shared_array).
+pub const TMA_ALIGN: usize = 128;
+
+/// Shared state space address of a generic pointer into shared memory
+#[inline]
+fn shared_addr<T>(ptr: *const T) -> u64 {
+    let addr: u64;
+    unsafe { asm!("cvta.to.shared.u64 {}, {};", out(reg64) addr, in(reg64) ptr) };
+    addr
+}
+
+/// A tensor map (`CUtensorMap`) for tiled bulk tensor copies of elements `T`
+/// with the box shape `Sh`. It is encoded on the host, e.g. with
+/// `cuTensorMapEncodeTiled`, and passed to the kernel by reference in global or
+/// constant memory.
+///
+/// Every value of this type must be a valid tiled tensor map. Its element type
+/// must match `T`, its box dimensions must be those of `Sh` in reverse order, as
+/// tensor maps list the innermost dimension first, and it must not be
+/// interleaved. Nothing ties it to a particular tensor, so the bulk copies
+/// leave it to their caller to use it with the array it describes.
+#[repr(C, align(64))]
+pub struct TensorMap<T, Sh: Shape> {
+    raw: [u8; 128],
+    marker: PhantomData<(*const T, Sh)>,
+}
+
+impl<T, Sh: Shape> TensorMap<T, Sh> {
+    /// # Safety
+    /// `raw` must be a tensor map as described for [`TensorMap`].
+    pub const unsafe fn from_raw(raw: [u8; 128]) -> Self {
+        Self {
+            raw,
+            marker: PhantomData,
+        }
+    }
+}
+
+/// A barrier in shared memory on which every thread of the block arrives and
+/// that additionally waits for the bytes of a bulk copy.
+///
+/// Each thread tracks the phase of the barrier, so every thread has to take
+/// part in every use.
+pub struct MBarrier<'s> {
+    ptr: *mut u64,
+    phase: u32,
+    storage: PhantomData<&'s mut u64>,
+}
+
+impl<'s> MBarrier<'s> {
+    /// Initializes the barrier for all threads of the block in the given
+    /// storage, e.g. `shared_array!(shared; u64; 1)`.
+    ///
+    /// # Safety
+    /// Must be called in a kernel uniform control flow state
+    pub unsafe fn new(storage: SimtArray<'s, u64, Block, Uninit, Dense, (Const<1>,)>) -> Self {
+        let threads = block_threads();
+        let addr = shared_addr(storage.ptr);
+        unsafe {
+            if linear_thread_idx() == 0 {
+                asm!(
+                    "mbarrier.init.shared::cta.b64 [{}], {};",
+                    in(reg64) addr,
+                    in(reg32) threads,
+                );
+                // Makes the initialization visible to the async proxy
+                asm!("fence.mbarrier_init.release.cluster;");
+            }
+            Block::sync();
+        }
+        Self {
+            ptr: storage.ptr,
+            phase: 0,
+            storage: PhantomData,
+        }
+    }
+
+    /// Arrives on the barrier and lets the current phase additionally wait
+    /// for `bytes` bytes of a bulk copy.
+    #[inline]
+    fn arrive_expect_tx(&self, bytes: u32) {
+        unsafe {
+            asm!(
+                "mbarrier.arrive.expect_tx.shared::cta.b64 _, [{}], {};",
+                in(reg64) shared_addr(self.ptr),
+                in(reg32) bytes,
+            )
+        }
+    }
+
+    #[inline]
+    fn arrive(&self) {
+        let addr = shared_addr(self.ptr);
+        unsafe { asm!("mbarrier.arrive.shared::cta.b64 _, [{}];", in(reg64) addr) }
+    }
+
+    /// Waits until the current phase completed and moves on to the next one.
+    #[inline]
+    fn wait(&mut self) {
+        let addr = shared_addr(self.ptr);
+        loop {
+            let done: u32;
+            unsafe {
+                asm!(
+                    "{{",
+                    ".reg .pred p;",
+                    "mbarrier.try_wait.parity.shared::cta.b64 p, [{addr}], {parity};",
+                    "selp.u32 {done}, 1, 0, p;",
+                    "}}",
+                    addr = in(reg64) addr,
+                    parity = in(reg32) self.phase,
+                    done = out(reg32) done,
+                )
+            };
+            if done != 0 {
+                break;
+            }
+        }
+        self.phase ^= 1;
+    }
+}
+
+/// A bulk tensor load into a tile that is still in flight. The tile is only
+/// handed out again by [`wait`](Self::wait).
+///
+/// Dropping the handle waits for the load as well, so that it never outlives
+/// the borrow of the tile. It must therefore not be leaked, see
+/// [`SimtArray::load_tile`].
+#[must_use = "the tile is only initialized after waiting for the load"]
+pub struct PendingLoad<'a, 'b, 's, T, Sh: Shape> {
+    tile: SimtArray<'s, T, Block, Uninit, Dense, Sh>,
+    barrier: &'a mut MBarrier<'b>,
+    source: PhantomData<&'a T>,
+}
+
+impl<'a, 'b, 's, T, Sh: Shape> PendingLoad<'a, 'b, 's, T, Sh> {
+    /// Waits until the tile arrived and returns it initialized.
+    pub fn wait(self) -> SimtArray<'s, T, Block, Init, Dense, Sh> {
+        let mut this = ManuallyDrop::new(self);
+        this.barrier.wait();
+        unsafe { SimtArray::new_unchecked(this.tile.ptr, this.tile.mapping) }
+    }
+}
+
+impl<'a, 'b, 's, T, Sh: Shape> Drop for PendingLoad<'a, 'b, 's, T, Sh> {
+    fn drop(&mut self) {
+        self.barrier.wait();
+    }
+}
+
+/// A bulk tensor store from a tile that is still in flight. The tile is only
+/// handed out again by [`wait`](Self::wait).
+///
+/// Dropping the handle waits for the store in the issuing thread. It must
+/// therefore not be leaked, see [`SimtArray::store_tile`].
+#[must_use = "the tile may only be reused after waiting for the store"]
+pub struct PendingStore<'a, 's, T, Sh: Shape> {
+    tile: SimtArray<'s, T, Block, Init, Dense, Sh>,
+    target: PhantomData<&'a mut T>,
+}
+
+/// Waits for all bulk stores the calling thread issued, including their writes
+/// to global memory
+#[inline]
+fn wait_stores() {
+    if linear_thread_idx() == 0 {
+        unsafe { asm!("cp.async.bulk.wait_group 0;") }
+    }
+}
+
+impl<'a, 's, T, Sh: Shape> PendingStore<'a, 's, T, Sh> {
+    /// Waits until the tile was written to global memory and returns it.
+    ///
+    /// # Safety
+    /// Must be called in a kernel uniform control flow state
+    pub unsafe fn wait(self) -> SimtArray<'s, T, Block, Init, Dense, Sh> {
+        let this = ManuallyDrop::new(self);
+        wait_stores();
+        unsafe {
+            Block::sync();
+            SimtArray::new_unchecked(this.tile.ptr, this.tile.mapping)
+        }
+    }
+}
+
+impl<'a, 's, T, Sh: Shape> Drop for PendingStore<'a, 's, T, Sh> {
+    fn drop(&mut self) {
+        wait_stores();
+    }
+}
+
+/// Implements the bulk tensor copies for tiles of one rank. The coordinates
+/// are given in the order of the array dimensions, `$coords` lists them for
+/// the instruction, innermost first.
+macro_rules! impl_tma {
+    (@i32 $c:ident) => { i32 };
+    ($rank:literal, ($($N:ident $c:ident),+), $coords:literal) => {
+        impl<'s, T, $(const $N: usize),+> SimtArray<'s, T, Block, Uninit, Dense, ($(Const<$N>,)+)> {
+            /// Starts loading the tile at the element coordinates `coords` of
+            /// `src` with the tensor map `map`. Coordinates outside of the tensor
+            /// are filled with zeros.
+            ///
+            /// # Panics
+            /// Panics if `src` has another rank or the tile is not aligned to
+            /// [`TMA_ALIGN`] bytes.
+            ///
+            /// # Safety
+            /// Must be called in a kernel uniform control flow state. `map` must
+            /// describe the tensor of `src`, i.e. its address, extents and strides.
+            /// The returned handle must be waited for or dropped and must not be
+            /// leaked, as the load would then outlive the borrows of the tile and
+            /// `src`.
+            pub unsafe fn load_tile<'a, 'b, I, L, Sg>(
+                self,
+                map: &'a TensorMap<T, ($(Const<$N>,)+)>,
+                src: &'a SimtArray<'_, T, Grid, I, L, Sg>,
+                barrier: &'a mut MBarrier<'b>,
+                ($($c,)+): ($(impl_tma!(@i32 $c),)+),
+            ) -> PendingLoad<'a, 'b, 's, T, ($(Const<$N>,)+)>
+            where
+                I: Viewable,
+                L: Layout,
+                Sg: Shape,
+            {
+                const {
+                    $(assert!($N > 0 && $N <= 256, "box dimensions must be in 1..=256");)+
+                }
+                let bytes = (1 $(* $N)+ * size_of::<T>()) as u32;
+                assert_eq!(src.mapping.rank(), $rank, "source has another rank");
+                let dst = shared_addr(self.ptr);
+                assert!(
+                    (dst as usize).is_multiple_of(TMA_ALIGN),
+                    "tile is not aligned to 128 bytes"
+                );
+                if linear_thread_idx() == 0 {
+                    barrier.arrive_expect_tx(bytes);
+                    unsafe {
+                        asm!(
+                            concat!(
+                                "cp.async.bulk.tensor.", $rank, "d.shared::cluster.global.tile",
+                                ".mbarrier::complete_tx::bytes [{dst}], [{map}, {{",
+                                $coords,
+                                "}}], [{bar}];"
+                            ),
+                            dst = in(reg64) dst,
+                            map = in(reg64) map.raw.as_ptr(),
+                            bar = in(reg64) shared_addr(barrier.ptr),
+                            $($c = in(reg32) $c,)+
+                        )
+                    };
+                } else {
+                    barrier.arrive();
+                }
+                PendingLoad {
+                    tile: self,
+                    barrier,
+                    source: PhantomData,
+                }
+            }
+        }
+
+        impl<'s, T, $(const $N: usize),+> SimtArray<'s, T, Block, Init, Dense, ($(Const<$N>,)+)> {
+            /// Starts storing the tile at the element coordinates `coords` of
+            /// `dst` with the tensor map `map`. Elements outside of the tensor
+            /// are skipped. Synchronizes the block first, so that the tile holds
+            /// the writes of every thread.
+            ///
+            /// # Panics
+            /// Panics if `dst` has another rank or the tile is not aligned to
+            /// [`TMA_ALIGN`] bytes.
+            ///
+            /// # Safety
+            /// Must be called in a kernel uniform control flow state. `map` must
+            /// describe the tensor of `dst`, i.e. its address, extents and strides.
+            /// The returned handle must be waited for or dropped and must not be
+            /// leaked, as the store would then outlive the borrows of the tile and
+            /// `dst`.
+            pub unsafe fn store_tile<'a, L, Sg>(
+                self,
+                map: &'a TensorMap<T, ($(Const<$N>,)+)>,
+                dst: &'a mut SimtArray<'_, T, Grid, Init, L, Sg>,
+                ($($c,)+): ($(impl_tma!(@i32 $c),)+),
+            ) -> PendingStore<'a, 's, T, ($(Const<$N>,)+)>
+            where
+                L: Layout,
+                Sg: Shape,
+            {
+                const {
+                    $(assert!($N > 0 && $N <= 256, "box dimensions must be in 1..=256");)+
+                }
+                assert_eq!(dst.mapping.rank(), $rank, "destination has another rank");
+                let src = shared_addr(self.ptr);
+                assert!(
+                    (src as usize).is_multiple_of(TMA_ALIGN),
+                    "tile is not aligned to 128 bytes"
+                );
+                unsafe {
+                    // Makes the writes of the threads visible to the async proxy
+                    asm!("fence.proxy.async.shared::cta;");
+                    Block::sync();
+                    if linear_thread_idx() == 0 {
+                        asm!(
+                            concat!(
+                                "cp.async.bulk.tensor.", $rank, "d.global.shared::cta.tile",
+                                ".bulk_group [{map}, {{",
+                                $coords,
+                                "}}], [{src}];"
+                            ),
+                            map = in(reg64) map.raw.as_ptr(),
+                            src = in(reg64) src,
+                            $($c = in(reg32) $c,)+
+                        );
+                        asm!("cp.async.bulk.commit_group;");
+                    }
+                }
+                PendingStore {
+                    tile: self,
+                    target: PhantomData,
+                }
+            }
+        }
+    };
+}
+
+impl_tma!(2, (N0 c0, N1 c1), "{c1}, {c0}");
+impl_tma!(3, (N0 c0, N1 c1, N2 c2), "{c2}, {c1}, {c0}");
+impl_tma!(4, (N0 c0, N1 c1, N2 c2, N3 c3), "{c3}, {c2}, {c1}, {c0}");
+impl_tma!(5, (N0 c0, N1 c1, N2 c2, N3 c3, N4 c4), "{c4}, {c3}, {c2}, {c1}, {c0}");
@@ -0,0 +1,245 @@
+//! Collectives between the threads of a warp that exchange values through
+//! `shfl.sync` instead of shared memory.
+//!
+//! All functions are called per thread. Every lane of the given [`LaneMask`]
+//! must call the same function with the same mask, lanes outside of the mask
+//! must not call it.
+use core::{
+    arch::asm,
+    mem::{size_of, transmute_copy},
+};
+
+use super::{WARP_SIZE, lane_id};
+
+/// The lanes of a warp that take part in a collective. Any non-empty set of
+/// lanes can be used, the collectives work on the rank of a lane inside of the
+/// mask, i.e. the number of lanes of the mask below it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LaneMask(u32);
+
+impl LaneMask {
+    /// All lanes of the warp
+    pub const FULL: Self = Self(u32::MAX);
+
+    /// The first `n` lanes of the warp
+    ///
+    /// # Panics
+    /// Panics if `n` is zero or larger than [`WARP_SIZE`].
+    pub const fn first(n: u32) -> Self {
+        assert!(
+            n > 0 && n <= WARP_SIZE,
+            "lane mask must contain 1 to 32 lanes"
+        );
+        Self(u32::MAX >> (WARP_SIZE - n))
+    }
+    /// The lanes whose bits are set in `bits`
+    ///
+    /// # Panics
+    /// Panics if `bits` is zero.
+    pub const fn from_bits(bits: u32) -> Self {
+        assert!(bits != 0, "lane mask must contain at least one lane");
+        Self(bits)
+    }
+    /// The lanes of the warp that currently execute, e.g. all existing lanes
+    /// of a partial last warp when called in uniform control flow.
+    #[inline]
+    pub fn active() -> Self {
+        let bits: u32;
+        unsafe { asm!("activemask.b32 {};", out(reg32) bits) };
+        Self(bits)
+    }
+    /// Number of lanes in the mask
+    pub const fn count(self) -> u32 {
+        self.0.count_ones()
+    }
+    /// The raw `membermask` operand
+    pub const fn bits(self) -> u32 {
+        self.0
+    }
+    pub const fn contains(self, lane: u32) -> bool {
+        lane < WARP_SIZE && self.0 & (1 << lane) != 0
+    }
+    /// Number of lanes of the mask below `lane`
+    pub const fn rank(self, lane: u32) -> u32 {
+        (self.0 & !(u32::MAX << lane)).count_ones()
+    }
+    /// The lane with the given rank
+    ///
+    /// # Panics
+    /// Panics if `rank` is not smaller than [`count`](Self::count).
+    pub const fn lane(self, rank: u32) -> u32 {
+        assert!(rank < self.count(), "rank is outside of the lane mask");
+        let mut bits = self.0;
+        let mut i = 0;
+        while i < rank {
+            bits &= bits - 1;
+            i += 1;
+        }
+        bits.trailing_zeros()
+    }
+}
+
+/// Issues a `shfl.sync` of the given mode on a 32 bit value, must be expanded
+/// in an unsafe context
+macro_rules! shfl {
+    ($mode:literal, $clamp:literal, $mask:expr, $value:expr, $b:expr) => {{
+        let out: u32;
+        asm!(
+            concat!("shfl.sync.", $mode, ".b32 {}, {}, {}, ", $clamp, ", {};"),
+            out(reg32) out,
+            in(reg32) $value,
+            in(reg32) $b,
+            in(reg32) $mask,
+        );
+        out
+    }};
+}
+
+/// Applies a 32 bit shuffle to each half of a 32 or 64 bit value
+#[inline]
+unsafe fn shuffle<T: Copy>(value: T, f: impl Fn(u32) -> u32) -> T {
+    const {
+        assert!(
+            size_of::<T>() == 4 || size_of::<T>() == 8,
+            "only 32 and 64 bit values can be shuffled"
+        )
+    };
+    unsafe {
+        if size_of::<T>() == 4 {
+            transmute_copy(&f(transmute_copy::<T, u32>(&value)))
+        } else {
+            let bits = transmute_copy::<T, u64>(&value);
+            let lo = f(bits as u32) as u64;
+            let hi = f((bits >> 32) as u32) as u64;
+            transmute_copy(&(lo | hi << 32))
+        }
+    }
+}
+
+/// Returns `value` of lane `lane_id - delta`, or the own value if that lane
+/// does not exist. If it exists but is not part of `mask`, the result is
+/// unspecified.
+///
+/// # Safety
+/// Every lane of `mask` must call this function with the same `mask` and
+/// `delta`. `T` must be 4 or 8 bytes large and must not contain padding.
+#[inline]
+pub unsafe fn shfl_up<T: Copy>(mask: LaneMask, value: T, delta: u32) -> T {
+    unsafe { shuffle(value, |v| shfl!("up", "0", mask.0, v, delta)) }
+}
+
+/// Returns `value` of lane `lane_id + delta`, or the own value if that lane
+/// does not exist.
+///
+/// # Safety
+/// Same as [`shfl_up`]. If lane `lane_id + delta` is not part of `mask`, the
+/// result is unspecified.
+#[inline]
+pub unsafe fn shfl_down<T: Copy>(mask: LaneMask, value: T, delta: u32) -> T {
+    unsafe { shuffle(value, |v| shfl!("down", "31", mask.0, v, delta)) }
+}
+
+/// Returns `value` of lane `lane_id ^ lane_mask`.
+///
+/// # Safety
+/// Same as [`shfl_up`]. If lane `lane_id ^ lane_mask` is not part of `mask`,
+/// the result is unspecified.
+#[inline]
+pub unsafe fn shfl_xor<T: Copy>(mask: LaneMask, value: T, lane_mask: u32) -> T {
+    unsafe { shuffle(value, |v| shfl!("bfly", "31", mask.0, v, lane_mask)) }
+}
+
+/// Returns `value` of lane `src_lane`.
+///
+/// # Safety
+/// Same as [`shfl_up`]. `src_lane` must be part of `mask`.
+#[inline]
+pub unsafe fn shfl_idx<T: Copy>(mask: LaneMask, value: T, src_lane: u32) -> T {
+    unsafe { shuffle(value, |v| shfl!("idx", "31", mask.0, v, src_lane)) }
+}
+
+/// Returns `value` of lane `src_lane` to every lane of `mask`.
+///
+/// # Safety
+/// Same as [`shfl_idx`].
+#[inline]
+pub unsafe fn broadcast<T: Copy>(mask: LaneMask, value: T, src_lane: u32) -> T {
+    unsafe { shfl_idx(mask, value, src_lane) }
+}
+
+/// Combines the values of all lanes of `mask` with `op` and returns the result
+/// to every one of them. `op` must be associative and commutative.
+///
+/// # Safety
+/// Same as [`shfl_up`].
+#[inline]
+pub unsafe fn reduce<T: Copy>(mask: LaneMask, mut value: T, mut op: impl FnMut(T, T) -> T) -> T {
+    let lane = lane_id();
+    let rank = mask.rank(lane);
+    let mut delta = mask.count().next_power_of_two() / 2;
+    while delta > 0 {
+        let src = if rank + delta < mask.count() {
+            mask.lane(rank + delta)
+        } else {
+            lane
+        };
+        let other = unsafe { shfl_idx(mask, value, src) };
+        if src != lane {
+            value = op(value, other);
+        }
+        delta /= 2;
+    }
+    unsafe { broadcast(mask, value, mask.lane(0)) }
+}
+
+/// Returns `op` applied to the values of all lanes of `mask` up to and
+/// including the calling one. `op` must be associative.
+///
+/// # Safety
+/// Same as [`shfl_up`].
+#[inline]
+pub unsafe fn inclusive_scan<T: Copy>(
+    mask: LaneMask,
+    mut value: T,
+    mut op: impl FnMut(T, T) -> T,
+) -> T {
+    let lane = lane_id();
+    let rank = mask.rank(lane);
+    let mut delta = 1;
+    while delta < mask.count() {
+        let src = if rank >= delta {
+            mask.lane(rank - delta)
+        } else {
+            lane
+        };
+        let other = unsafe { shfl_idx(mask, value, src) };
+        if src != lane {
+            value = op(other, value);
+        }
+        delta *= 2;
+    }
+    value
+}
+
+/// Returns `op` applied to `init` and the values of all lanes of `mask` before
+/// the calling one, so the first lane receives `init`. `op` must be associative.
+///
+/// # Safety
+/// Same as [`shfl_up`].
+#[inline]
+pub unsafe fn exclusive_scan<T: Copy>(
+    mask: LaneMask,
+    value: T,
+    init: T,
+    mut op: impl FnMut(T, T) -> T,
+) -> T {
+    let inclusive = unsafe { inclusive_scan(mask, value, &mut op) };
+    let rank = mask.rank(lane_id());
+    let src = if rank > 0 {
+        mask.lane(rank - 1)
+    } else {
+        lane_id()
+    };
+    let previous = unsafe { shfl_idx(mask, inclusive, src) };
+    if rank == 0 { init } else { op(init, previous) }
+}
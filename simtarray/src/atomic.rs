@@ -0,0 +1,215 @@
+use core::{cell::UnsafeCell, marker::PhantomData};
+
+use mdarray::{Layout, Shape, View};
+
+use crate::{Scope, SimtArray, Viewable};
+
+/// Element types that support relaxed atomic operations visible to all units
+/// of scope `Sc`.
+///
+/// # Safety
+/// Every function must perform a single atomic operation on `ptr` that is at
+/// least as wide as `Sc` and return the previous value.
+pub unsafe trait AtomicElement<Sc: Scope>: Copy {
+    /// # Safety
+    /// `ptr` must be valid, aligned and initialized.
+    unsafe fn load(ptr: *mut Self) -> Self;
+    /// # Safety
+    /// See [`load`](Self::load).
+    unsafe fn fetch_add(ptr: *mut Self, value: Self) -> Self;
+    /// # Safety
+    /// See [`load`](Self::load).
+    unsafe fn fetch_min(ptr: *mut Self, value: Self) -> Self;
+    /// # Safety
+    /// See [`load`](Self::load).
+    unsafe fn fetch_max(ptr: *mut Self, value: Self) -> Self;
+    /// Compares bitwise.
+    ///
+    /// # Safety
+    /// See [`load`](Self::load).
+    unsafe fn compare_exchange(ptr: *mut Self, current: Self, new: Self) -> Result<Self, Self>;
+}
+
+/// An element of a [`SimtArray`] that any unit of scope `Sc` may update.
+///
+/// All operations are relaxed, they are ordered with respect to other memory
+/// accesses only by syncing the scope.
+#[repr(transparent)]
+pub struct Atomic<T, Sc> {
+    value: UnsafeCell<T>,
+    scope: PhantomData<Sc>,
+}
+
+impl<T: AtomicElement<Sc>, Sc: Scope> Atomic<T, Sc> {
+    #[inline]
+    pub fn load(&self) -> T {
+        unsafe { T::load(self.value.get()) }
+    }
+    /// Adds `value` and returns the previous value. Integers wrap around.
+    #[inline]
+    pub fn fetch_add(&self, value: T) -> T {
+        unsafe { T::fetch_add(self.value.get(), value) }
+    }
+    /// Stores the minimum of the current value and `value` and returns the
+    /// previous value.
+    #[inline]
+    pub fn fetch_min(&self, value: T) -> T {
+        unsafe { T::fetch_min(self.value.get(), value) }
+    }
+    /// Stores the maximum of the current value and `value` and returns the
+    /// previous value.
+    #[inline]
+    pub fn fetch_max(&self, value: T) -> T {
+        unsafe { T::fetch_max(self.value.get(), value) }
+    }
+    /// Stores `new` if the current value is bitwise equal to `current`. Returns
+    /// the previous value, as `Ok` if it was replaced.
+    #[inline]
+    pub fn compare_exchange(&self, current: T, new: T) -> Result<T, T> {
+        unsafe { T::compare_exchange(self.value.get(), current, new) }
+    }
+}
+
+impl<'s, T, Sc: Scope, I: Viewable, L: Layout, Sh: Shape> SimtArray<'s, T, Sc, I, L, Sh> {
+    /// Returns a view of the whole array through which every unit of the scope
+    /// may update any element atomically. The array is borrowed mutably, so no
+    /// plain view of it can be alive at the same time.
+    pub fn view_atomic<'a>(&'a mut self) -> View<'a, Atomic<T, Sc>, Sh, L> {
+        unsafe { View::new_unchecked(self.ptr as *const Atomic<T, Sc>, self.mapping.clone()) }
+    }
+}
+
+/// Replaces the value by `f(old)` until it succeeds or `f` returns `None`
+/// and returns the previous value.
+#[inline]
+pub(crate) unsafe fn update<T, Sc>(ptr: *mut T, mut f: impl FnMut(T) -> Option<T>) -> T
+where
+    T: AtomicElement<Sc>,
+    Sc: Scope,
+{
+    let mut old = unsafe { T::load(ptr) };
+    while let Some(new) = f(old) {
+        match unsafe { T::compare_exchange(ptr, old, new) } {
+            Ok(_) => break,
+            Err(actual) => old = actual,
+        }
+    }
+    old
+}
+
+/// Atomics of `core` are at least device wide, so they fit every scope.
+#[cfg(not(target_arch = "nvptx64"))]
+mod core_atomics {
+    use core::sync::atomic::{AtomicI32, AtomicI64, AtomicU32, AtomicU64, Ordering::Relaxed};
+
+    use super::{AtomicElement, update};
+    use crate::Scope;
+
+    macro_rules! impl_atomic_int {
+        ($(($T:ty, $A:ty)),+) => {
+            $(
+                unsafe impl<Sc: Scope> AtomicElement<Sc> for $T {
+                    #[inline]
+                    unsafe fn load(ptr: *mut Self) -> Self {
+                        unsafe { <$A>::from_ptr(ptr) }.load(Relaxed)
+                    }
+                    #[inline]
+                    unsafe fn fetch_add(ptr: *mut Self, value: Self) -> Self {
+                        unsafe { <$A>::from_ptr(ptr) }.fetch_add(value, Relaxed)
+                    }
+                    #[inline]
+                    unsafe fn fetch_min(ptr: *mut Self, value: Self) -> Self {
+                        unsafe { <$A>::from_ptr(ptr) }.fetch_min(value, Relaxed)
+                    }
+                    #[inline]
+                    unsafe fn fetch_max(ptr: *mut Self, value: Self) -> Self {
+                        unsafe { <$A>::from_ptr(ptr) }.fetch_max(value, Relaxed)
+                    }
+                    #[inline]
+                    unsafe fn compare_exchange(
+                        ptr: *mut Self,
+                        current: Self,
+                        new: Self,
+                    ) -> Result<Self, Self> {
+                        unsafe { <$A>::from_ptr(ptr) }
+                            .compare_exchange(current, new, Relaxed, Relaxed)
+                    }
+                }
+            )+
+        };
+    }
+
+    impl_atomic_int!(
+        (u32, AtomicU32),
+        (i32, AtomicI32),
+        (u64, AtomicU64),
+        (i64, AtomicI64)
+    );
+
+    /// Floats are updated through a compare exchange loop on their bits
+    macro_rules! impl_atomic_float {
+        ($(($T:ty, $A:ty)),+) => {
+            $(
+                unsafe impl<Sc: Scope> AtomicElement<Sc> for $T {
+                    #[inline]
+                    unsafe fn load(ptr: *mut Self) -> Self {
+                        <$T>::from_bits(unsafe { <$A>::from_ptr(ptr.cast()) }.load(Relaxed))
+                    }
+                    #[inline]
+                    unsafe fn fetch_add(ptr: *mut Self, value: Self) -> Self {
+                        unsafe { update::<Self, Sc>(ptr, |old| Some(old + value)) }
+                    }
+                    #[inline]
+                    unsafe fn fetch_min(ptr: *mut Self, value: Self) -> Self {
+                        unsafe { update::<Self, Sc>(ptr, |old| (value < old).then_some(value)) }
+                    }
+                    #[inline]
+                    unsafe fn fetch_max(ptr: *mut Self, value: Self) -> Self {
+                        unsafe { update::<Self, Sc>(ptr, |old| (value > old).then_some(value)) }
+                    }
+                    #[inline]
+                    unsafe fn compare_exchange(
+                        ptr: *mut Self,
+                        current: Self,
+                        new: Self,
+                    ) -> Result<Self, Self> {
+                        unsafe { <$A>::from_ptr(ptr.cast()) }
+                            .compare_exchange(current.to_bits(), new.to_bits(), Relaxed, Relaxed)
+                            .map(<$T>::from_bits)
+                            .map_err(<$T>::from_bits)
+                    }
+                }
+            )+
+        };
+    }
+
+    impl_atomic_float!((f32, AtomicU32), (f64, AtomicU64));
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec;
+
+    use crate::{
+        Init, Projection,
+        host::{Buffer, Grid, Thread, Xyz, launch},
+    };
+
+    #[test]
+    fn view_atomic() {
+        let bins = Buffer::new(vec![0u32; 4]);
+        let extrema = Buffer::new(vec![0f32; 2]);
+        launch(2, 8, || {
+            let idx = <Xyz as Projection<Thread, Grid>>::idx();
+            let mut bins = bins.array::<Grid, Init, _>(0, (4,));
+            bins.view_atomic()[[idx as usize % 4]].fetch_add(1);
+            let mut extrema = extrema.array::<Grid, Init, _>(0, (2,));
+            let view = extrema.view_atomic();
+            view[[0]].fetch_min(-(idx as f32));
+            view[[1]].fetch_max(idx as f32 / 2.0);
+            assert!(view[[1]].compare_exchange(-1.0, 0.0).is_err());
+        });
+        assert_eq!(bins.into_vec(), [4, 4, 4, 4]);
+        assert_eq!(extrema.into_vec(), [-15.0, 7.5]);
+    }
+}
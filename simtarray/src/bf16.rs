@@ -0,0 +1,43 @@
+/// A bfloat16 value, stored as its bits.
+#[repr(transparent)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Bf16(pub u16);
+
+impl Bf16 {
+    /// Rounds to the nearest value, ties to even. NaN stays NaN.
+    pub fn from_f32(value: f32) -> Self {
+        let bits = value.to_bits();
+        if value.is_nan() {
+            return Self((bits >> 16) as u16 | 0x40);
+        }
+        let round = 0x7fff + ((bits >> 16) & 1);
+        Self((bits.wrapping_add(round) >> 16) as u16)
+    }
+    pub fn to_f32(self) -> f32 {
+        f32::from_bits((self.0 as u32) << 16)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_f32() {
+        let bf16 = |bits: u32| Bf16::from_f32(f32::from_bits(bits)).0;
+        assert_eq!(bf16(0x3f80_0000), 0x3f80);
+        assert_eq!(bf16(0x3f80_8001), 0x3f81);
+        // Ties round to the even value
+        assert_eq!(bf16(0x3f80_8000), 0x3f80);
+        assert_eq!(bf16(0x3f81_8000), 0x3f82);
+        assert_eq!(bf16(0xbf81_8000), 0xbf82);
+        assert_eq!(Bf16::from_f32(f32::MAX).to_f32(), f32::INFINITY);
+        assert!(
+            Bf16::from_f32(f32::from_bits(0x7f80_0001))
+                .to_f32()
+                .is_nan()
+        );
+        assert!(Bf16::from_f32(f32::NAN).to_f32().is_nan());
+        assert_eq!(Bf16::from_f32(-2.5).to_f32(), -2.5);
+    }
+}
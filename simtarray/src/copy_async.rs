@@ -0,0 +1,249 @@
+use core::marker::PhantomData;
+
+use mdarray::{Layout, Mapping, Shape};
+
+use crate::{
+    AsUsize, Init, ProjectionSetDim0, Scope, SimtArray, SyncableScope, Uninit, UnitScope, Viewable,
+};
+
+/// Scopes whose arrays can be filled by copies that run in the background,
+/// such as `Block` arrays in shared memory on sm_80+.
+///
+/// Copies are issued per unit and collected into groups. A unit waits only for
+/// its own groups, the scope has to be synchronized afterwards before other
+/// units may read the copied elements.
+///
+/// Groups are committed through the [`AsyncCopies`] of a unit, which counts
+/// them, so that a group can be waited for even if younger ones were committed.
+///
+/// # Safety
+/// Once [`wait_group`](Self::wait_group) returned, every copy of the calling
+/// unit that was committed before all but the last `N` commits must be complete.
+pub unsafe trait AsyncCopyScope: SyncableScope {
+    /// Starts copying `*src` to `dst`. Copies the hardware cannot run in the
+    /// background, e.g. from other shared memory or into a destination that is
+    /// not in shared memory, are done right away.
+    ///
+    /// # Safety
+    /// `dst` must point into an array of this scope and `src` into an array of
+    /// any scope of the same architecture. Both must be valid and aligned,
+    /// `*src` must be initialized and neither may be accessed until the copy
+    /// completed.
+    unsafe fn copy<T: Copy>(dst: *mut T, src: *const T);
+    /// Closes the group of copies the calling unit issued since the last commit.
+    ///
+    /// # Safety
+    /// Must only be called by [`AsyncCopies`], which counts the groups.
+    unsafe fn commit();
+    /// Blocks until at most `N` of the groups the calling unit committed are
+    /// still pending.
+    fn wait_group<const N: u32>();
+}
+
+/// The groups of async copies the calling unit committed. Every copy is
+/// issued through it, so that the groups can be waited for one by one.
+pub struct AsyncCopies<Sc: AsyncCopyScope> {
+    committed: usize,
+    scope: PhantomData<Sc>,
+}
+
+impl<Sc: AsyncCopyScope> AsyncCopies<Sc> {
+    /// # Safety
+    /// At most one may exist per unit at a time, as otherwise the groups of the
+    /// other one are not counted.
+    pub unsafe fn new() -> Self {
+        Self {
+            committed: 0,
+            scope: PhantomData,
+        }
+    }
+
+    /// Commits the copies issued since the last commit as one group and returns
+    /// its number.
+    #[inline]
+    pub(crate) fn commit(&mut self) -> usize {
+        unsafe { Sc::commit() };
+        self.committed += 1;
+        self.committed - 1
+    }
+
+    /// Blocks until the group with the given number is complete. Larger
+    /// numbers of younger groups than the instruction can encode wait for
+    /// some of those as well.
+    #[inline]
+    pub(crate) fn wait_for(&self, group: usize) {
+        match self.committed - group - 1 {
+            0 => Sc::wait_group::<0>(),
+            1 => Sc::wait_group::<1>(),
+            2 => Sc::wait_group::<2>(),
+            3 => Sc::wait_group::<3>(),
+            4 => Sc::wait_group::<4>(),
+            5 => Sc::wait_group::<5>(),
+            6 => Sc::wait_group::<6>(),
+            _ => Sc::wait_group::<7>(),
+        }
+    }
+}
+
+/// Copies into an array that are still in flight. The array is only handed out
+/// again by [`wait`](Self::wait).
+///
+/// Dropping the handle waits for the copies of the calling unit, so that they
+/// never outlive the borrow of either array. It must therefore not be leaked,
+/// see [`SimtArray::copy_from_async`].
+#[must_use = "the array is only initialized after waiting for the copy"]
+pub struct PendingCopy<'a, 's, T, Sc: AsyncCopyScope, L: Layout, Sh: Shape> {
+    array: SimtArray<'s, T, Sc, Uninit, L, Sh>,
+    source: PhantomData<&'a T>,
+}
+
+impl<'a, 's, T, Sc: AsyncCopyScope, L: Layout, Sh: Shape> PendingCopy<'a, 's, T, Sc, L, Sh> {
+    /// Waits until the copies of all units completed and returns the
+    /// initialized array.
+    ///
+    /// # Safety
+    /// Must be called in a kernel uniform control flow state
+    pub unsafe fn wait(self) -> SimtArray<'s, T, Sc, Init, L, Sh> {
+        Sc::wait_group::<0>();
+        unsafe {
+            Sc::sync();
+            SimtArray::new_unchecked(self.array.ptr, self.array.mapping.clone())
+        }
+    }
+}
+
+impl<'a, 's, T, Sc: AsyncCopyScope, L: Layout, Sh: Shape> Drop
+    for PendingCopy<'a, 's, T, Sc, L, Sh>
+{
+    fn drop(&mut self) {
+        Sc::wait_group::<0>();
+    }
+}
+
+impl<'s, T: Copy, Sc: AsyncCopyScope, L: Layout, Sh: Shape> SimtArray<'s, T, Sc, Uninit, L, Sh> {
+    /// Starts copying `src` into the array. The elements are distributed
+    /// cyclically in row major order over the units of scope `E`, so that
+    /// neighbouring units copy neighbouring elements. `src` can be of any
+    /// scope, e.g. a `Grid` array or its `Block` tile.
+    ///
+    /// # Panics
+    /// Panics if the dimensions of `src` differ from those of the array.
+    ///
+    /// # Safety
+    /// Must be called in a kernel uniform control flow state. The returned
+    /// handle must be waited for or dropped and must not be leaked, e.g. with
+    /// [`mem::forget`](core::mem::forget), as the copies would then outlive the
+    /// borrows of both arrays.
+    pub unsafe fn copy_from_async<'a, E, Ps, So, I, Ls>(
+        self,
+        copies: &mut AsyncCopies<Sc>,
+        src: &'a SimtArray<'_, T, So, I, Ls, Sh>,
+    ) -> PendingCopy<'a, 's, T, Sc, L, Sh>
+    where
+        E: UnitScope<Arch = Sc::Arch>,
+        Ps: ProjectionSetDim0<(usize,), E, Sc, Arch = Sc::Arch>,
+        So: Scope<Arch = Sc::Arch>,
+        I: Viewable,
+        Ls: Layout,
+    {
+        self.issue_copy::<E, Ps, So, I, Ls>(copies, src);
+        PendingCopy {
+            array: self,
+            source: PhantomData,
+        }
+    }
+
+    /// Issues the copies of the calling unit and commits them as one group,
+    /// leaving it to the caller to wait for them. Returns the number of the
+    /// group.
+    pub(crate) fn issue_copy<E, Ps, So, I, Ls>(
+        &self,
+        copies: &mut AsyncCopies<Sc>,
+        src: &SimtArray<'_, T, So, I, Ls, Sh>,
+    ) -> usize
+    where
+        E: UnitScope<Arch = Sc::Arch>,
+        Ps: ProjectionSetDim0<(usize,), E, Sc, Arch = Sc::Arch>,
+        So: Scope<Arch = Sc::Arch>,
+        I: Viewable,
+        Ls: Layout,
+    {
+        let units: usize = Ps::dim0().as_();
+        let idx: usize = Ps::idx0().as_();
+        assert!(
+            (0..self.mapping.rank()).all(|i| self.mapping.dim(i) == src.mapping.dim(i)),
+            "source and destination shapes differ"
+        );
+        for k in (idx..self.mapping.len()).step_by(units) {
+            unsafe {
+                Sc::copy(
+                    self.ptr.offset(offset(&self.mapping, k)),
+                    src.ptr.offset(offset(&src.mapping, k)),
+                )
+            };
+        }
+        copies.commit()
+    }
+}
+
+/// Offset of the element with the row major linear index `k`
+#[inline]
+fn offset<M: Mapping>(mapping: &M, mut k: usize) -> isize {
+    let mut offset = 0;
+    for i in (0..mapping.rank()).rev() {
+        let dim = mapping.dim(i);
+        offset += (k % dim) as isize * mapping.stride(i);
+        k /= dim;
+    }
+    offset
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+
+    use super::*;
+    use crate::{
+        Blocked, Projection,
+        host::{Block, Buffer, Grid, Thread, Xyz, launch},
+    };
+
+    #[test]
+    fn copy_from_async() {
+        let src = Buffer::new((0..24u32).collect::<Vec<_>>());
+        let dst = Buffer::new(std::vec![0u32; 24]);
+        launch(1, (2, 3), || {
+            let mut copies = unsafe { AsyncCopies::new() };
+            let src = src.array::<Grid, Init, _>(0, (4, 6));
+            let dst = unsafe {
+                dst.array::<Block, Uninit, _>(0, (4, 6))
+                    .copy_from_async::<Thread, (Xyz,), _, _, _>(&mut copies, &src)
+                    .wait()
+            };
+            assert_eq!(dst.expr()[[3, 5]], 23);
+            assert_eq!(dst.expr()[[1, 2]], 8);
+        });
+        assert!(dst.into_vec().into_iter().eq(0..24));
+    }
+
+    #[test]
+    fn copy_from_block_tile() {
+        let src = Buffer::new((0..48u32).collect::<Vec<_>>());
+        let dst = Buffer::new(std::vec![0u32; 48]);
+        launch(2, (2, 3), || {
+            let mut copies = unsafe { AsyncCopies::new() };
+            let block = <Xyz as Projection<Block, Grid>>::idx() as usize;
+            let tile = src
+                .array::<Grid, Init, _>(0, (48,))
+                .split::<Block, (Xyz,), Blocked>()
+                .unwrap();
+            let dst = unsafe {
+                dst.array::<Block, Uninit, _>(block * 24, (24,))
+                    .copy_from_async::<Thread, (Xyz,), _, _, _>(&mut copies, &tile)
+                    .wait()
+            };
+            assert_eq!(dst.expr()[[23]], block as u32 * 24 + 23);
+        });
+        assert!(dst.into_vec().into_iter().eq(0..48));
+    }
+}
@@ -0,0 +1,145 @@
+use core::{fmt, marker::PhantomData};
+
+// Arrays over device slices need the `Grid` of an architecture
+#[cfg(any(
+    target_arch = "nvptx64",
+    target_arch = "amdgpu",
+    test,
+    feature = "host"
+))]
+use {
+    crate::{AsUsize, Init, SimtArray, Unsigned, kernel::Grid},
+    mdarray::{Dense, DenseMapping, Mapping, Shape},
+    num_traits::Bounded,
+};
+
+/// A slice of device memory as it is passed to a kernel: a pointer and the
+/// number of elements behind it.
+#[repr(C)]
+pub struct DeviceSlice<'a, T> {
+    ptr: *mut T,
+    len: usize,
+    marker: PhantomData<&'a mut [T]>,
+}
+
+impl<'a, T> DeviceSlice<'a, T> {
+    /// # Safety
+    /// If `ptr` is not null and aligned, it must be valid for reads and writes
+    /// of `len` elements of `T` for `'a` and must not be accessed through other
+    /// pointers in the meantime. The elements must be initialized.
+    pub unsafe fn from_raw_parts(ptr: *mut T, len: usize) -> Self {
+        Self {
+            ptr,
+            len,
+            marker: PhantomData,
+        }
+    }
+    pub fn len(&self) -> usize {
+        self.len
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl<'a, T> From<&'a mut [T]> for DeviceSlice<'a, T> {
+    fn from(slice: &'a mut [T]) -> Self {
+        unsafe { Self::from_raw_parts(slice.as_mut_ptr(), slice.len()) }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FromSliceError {
+    /// The pointer of the slice is null
+    Null,
+    /// The pointer of the slice is not aligned for the element type
+    Misaligned,
+    /// The shape has more elements than the index size of the architecture
+    /// can address
+    IndexOverflow,
+    /// The shape has more elements than the slice
+    TooShort { required: usize, len: usize },
+}
+
+impl fmt::Display for FromSliceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Null => write!(f, "device slice is null"),
+            Self::Misaligned => write!(f, "device slice is not aligned"),
+            Self::IndexOverflow => write!(f, "shape exceeds the index size of the architecture"),
+            Self::TooShort { required, len } => {
+                write!(f, "shape requires {required} elements but slice has {len}")
+            }
+        }
+    }
+}
+
+impl core::error::Error for FromSliceError {}
+
+#[cfg(any(
+    target_arch = "nvptx64",
+    target_arch = "amdgpu",
+    test,
+    feature = "host"
+))]
+impl<'s, T, Sh: Shape> SimtArray<'s, T, Grid, Init, Dense, Sh> {
+    /// Creates a dense array of the given shape over a kernel argument. Only
+    /// the grid can share it, since the blocks of a grid cannot synchronize.
+    pub fn from_device_slice(slice: DeviceSlice<'s, T>, shape: Sh) -> Result<Self, FromSliceError> {
+        if slice.ptr.is_null() {
+            return Err(FromSliceError::Null);
+        }
+        if !slice.ptr.is_aligned() {
+            return Err(FromSliceError::Misaligned);
+        }
+        let mapping = DenseMapping::new(shape);
+        let required = (0..mapping.rank())
+            .try_fold(1usize, |len, i| len.checked_mul(mapping.dim(i)))
+            .filter(|&len| len <= <Unsigned<Grid> as Bounded>::max_value().as_())
+            .ok_or(FromSliceError::IndexOverflow)?;
+        if required > slice.len {
+            return Err(FromSliceError::TooShort {
+                required,
+                len: slice.len,
+            });
+        }
+        Ok(unsafe { Self::new_unchecked(slice.ptr, mapping) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec;
+
+    use super::*;
+    use crate::host::Grid;
+
+    #[test]
+    fn from_device_slice() {
+        type Array<'s> = SimtArray<'s, u32, Grid, Init, Dense, (usize, usize)>;
+        let mut data = vec![0u32; 12];
+        assert!(Array::from_device_slice(data.as_mut_slice().into(), (3, 4)).is_ok());
+        assert_eq!(
+            Array::from_device_slice(data.as_mut_slice().into(), (4, 4)).err(),
+            Some(FromSliceError::TooShort {
+                required: 16,
+                len: 12
+            })
+        );
+        assert_eq!(
+            Array::from_device_slice(data.as_mut_slice().into(), (1 << 16, 1 << 16)).err(),
+            Some(FromSliceError::IndexOverflow)
+        );
+        let null = unsafe { DeviceSlice::from_raw_parts(core::ptr::null_mut(), 0) };
+        assert_eq!(
+            Array::from_device_slice(null, (0, 0)).err(),
+            Some(FromSliceError::Null)
+        );
+        let misaligned =
+            unsafe { DeviceSlice::from_raw_parts(data.as_mut_ptr().byte_add(1), data.len() - 1) };
+        assert_eq!(
+            Array::from_device_slice(misaligned, (1, 1)).err(),
+            Some(FromSliceError::Misaligned)
+        );
+    }
+}
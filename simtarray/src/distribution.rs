@@ -0,0 +1,236 @@
+use mdarray::{Const, Dim, Shape, StridedMapping};
+
+/// Describes how the elements of one dimension are dealt out to the units
+/// splitting it.
+///
+/// # Safety
+/// For every `len` and `units`, the parts of all `idx < units` must be
+/// disjoint, must lie inside of `0..len` and must together cover `0..len`.
+/// [`part`](Self::part), [`indices`](Self::indices) and
+/// [`run_len`](Self::run_len) must describe the same elements.
+pub unsafe trait Distribution {
+    /// Shape of the part a single unit receives
+    type Part: Shape;
+
+    /// Returns the offset and the mapping of the part of unit `idx` out of
+    /// `units` inside of a dimension with `len` elements that are `stride`
+    /// elements apart, or `None` if the unit does not get any element.
+    fn part(
+        len: usize,
+        stride: isize,
+        idx: usize,
+        units: usize,
+    ) -> Option<(isize, StridedMapping<Self::Part>)>;
+
+    /// Indices inside of the dimension of all elements of the part of unit `idx`
+    fn indices(len: usize, idx: usize, units: usize) -> impl Iterator<Item = usize>;
+
+    /// Length of the contiguous runs that are dealt round-robin to the units,
+    /// i.e. unit `idx` gets the runs `idx, idx + units, ..`. Never zero.
+    fn run_len(len: usize, units: usize) -> usize;
+}
+
+/// Distributions that can split a dimension of type `D`. Splitting evaluates
+/// [`CHECK`](Self::CHECK), so that lengths a distribution cannot split fail to
+/// compile instead of panicking at run time.
+pub trait Distributes<D: Dim>: Distribution {
+    /// Fails to evaluate if the length of `D` cannot be split
+    const CHECK: () = ();
+}
+
+/// Index of an element along one dimension of the whole array, as passed to
+/// the closures of `write_once` and `init_with`. Its own type keeps it from
+/// being mistaken for the position of the element in the part of a unit.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct GlobalIndex(pub usize);
+
+/// Unit `i` gets the elements `i, i + n, i + 2n, ..`
+pub struct Cyclic;
+
+/// Unit `i` gets the `i`th of `n` contiguous chunks of `ceil(len / n)` elements.
+/// Trailing units may get a shorter chunk or no elements at all.
+pub struct Blocked;
+
+/// Unit `i` gets the tiles `i, i + n, i + 2n, ..` of `K` consecutive elements,
+/// e.g. `K = 4` for vectorized access. The part of a unit is indexed by
+/// `[tile, element]`.
+///
+/// Only splits dimensions with a static length that is a multiple of `K`, as
+/// the tail of any other length would not form a whole tile.
+pub struct BlockCyclic<const K: usize>;
+
+unsafe impl Distribution for Cyclic {
+    type Part = (usize,);
+
+    #[inline]
+    fn part(
+        len: usize,
+        stride: isize,
+        idx: usize,
+        units: usize,
+    ) -> Option<(isize, StridedMapping<Self::Part>)> {
+        if idx >= len {
+            return None;
+        }
+        let mapping =
+            StridedMapping::new(((len - idx).div_ceil(units),), &[stride * units as isize]);
+        Some((idx as isize * stride, mapping))
+    }
+
+    #[inline]
+    fn indices(len: usize, idx: usize, units: usize) -> impl Iterator<Item = usize> {
+        (idx..len).step_by(units)
+    }
+
+    #[inline]
+    fn run_len(_len: usize, _units: usize) -> usize {
+        1
+    }
+}
+
+impl Blocked {
+    #[inline]
+    fn chunk(len: usize, idx: usize, units: usize) -> core::ops::Range<usize> {
+        let chunk = len.div_ceil(units);
+        let start = (idx * chunk).min(len);
+        start..(start + chunk).min(len)
+    }
+}
+
+unsafe impl Distribution for Blocked {
+    type Part = (usize,);
+
+    #[inline]
+    fn part(
+        len: usize,
+        stride: isize,
+        idx: usize,
+        units: usize,
+    ) -> Option<(isize, StridedMapping<Self::Part>)> {
+        let chunk = Self::chunk(len, idx, units);
+        if chunk.is_empty() {
+            return None;
+        }
+        let mapping = StridedMapping::new((chunk.len(),), &[stride]);
+        Some((chunk.start as isize * stride, mapping))
+    }
+
+    #[inline]
+    fn indices(len: usize, idx: usize, units: usize) -> impl Iterator<Item = usize> {
+        Self::chunk(len, idx, units)
+    }
+
+    #[inline]
+    fn run_len(len: usize, units: usize) -> usize {
+        len.div_ceil(units).max(1)
+    }
+}
+
+impl<D: Dim> Distributes<D> for Cyclic {}
+
+impl<D: Dim> Distributes<D> for Blocked {}
+
+impl<const K: usize, const N: usize> Distributes<Const<N>> for BlockCyclic<K> {
+    const CHECK: () = assert!(
+        K > 0 && N.is_multiple_of(K),
+        "length is not a multiple of the tile width"
+    );
+}
+
+impl<const K: usize> BlockCyclic<K> {
+    #[inline]
+    fn tiles(len: usize) -> usize {
+        const { assert!(K > 0, "tile width must not be zero") };
+        assert!(
+            len.is_multiple_of(K),
+            "length {len} is not a multiple of the tile width {K}"
+        );
+        len / K
+    }
+}
+
+unsafe impl<const K: usize> Distribution for BlockCyclic<K> {
+    type Part = (usize, Const<K>);
+
+    #[inline]
+    fn part(
+        len: usize,
+        stride: isize,
+        idx: usize,
+        units: usize,
+    ) -> Option<(isize, StridedMapping<Self::Part>)> {
+        let tiles = Self::tiles(len);
+        if idx >= tiles {
+            return None;
+        }
+        let shape = ((tiles - idx).div_ceil(units), Const::<K>);
+        let mapping = StridedMapping::new(shape, &[stride * (K * units) as isize, stride]);
+        Some(((idx * K) as isize * stride, mapping))
+    }
+
+    #[inline]
+    fn indices(len: usize, idx: usize, units: usize) -> impl Iterator<Item = usize> {
+        (idx..Self::tiles(len))
+            .step_by(units)
+            .flat_map(|tile| tile * K..(tile + 1) * K)
+    }
+
+    #[inline]
+    fn run_len(_len: usize, _units: usize) -> usize {
+        K
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{vec, vec::Vec};
+
+    use super::*;
+    use crate::{
+        Projection, Uninit,
+        host::{Block, Buffer, Thread, Xyz, launch},
+    };
+
+    #[test]
+    fn blocked() {
+        let data = Buffer::new(vec![0u32; 10]);
+        launch(1, 4, || {
+            let array = unsafe {
+                data.array::<Block, Uninit, _>(0, (10,))
+                    .init_with::<Thread, (Xyz,), Blocked, _>(|(GlobalIndex(i),)| i as u32)
+            };
+            let idx = <Xyz as Projection<Thread, Block>>::idx() as usize;
+            let view = array.view::<Thread, (Xyz,), Blocked>().unwrap();
+            assert_eq!(view.len(), if idx == 3 { 1 } else { 3 });
+            for i in 0..view.len() {
+                assert_eq!(view[[i]], (idx * 3 + i) as u32);
+            }
+        });
+        assert_eq!(data.into_vec(), (0..10).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn block_cyclic() {
+        let data = Buffer::new(vec![0u32; 16]);
+        launch(1, 2, || {
+            let mut array = unsafe {
+                data.array::<Block, Uninit, _>(0, (Const::<16>,))
+                    .init_with::<Thread, (Xyz,), BlockCyclic<4>, _>(|(GlobalIndex(i),)| i as u32)
+            };
+            let idx = <Xyz as Projection<Thread, Block>>::idx() as usize;
+            {
+                let view = array.view::<Thread, (Xyz,), BlockCyclic<4>>().unwrap();
+                assert_eq!(view.dim(0), 2);
+                for tile in 0..2 {
+                    for i in 0..4 {
+                        assert_eq!(view[[tile, i]], ((tile * 2 + idx) * 4 + i) as u32);
+                    }
+                }
+            }
+            let mut part = unsafe { array.view_mut::<Thread, (Xyz,), BlockCyclic<4>>() };
+            part.expr_mut().unwrap()[[1, 3]] = 100;
+        });
+        let data = data.into_vec();
+        assert_eq!((data[11], data[15]), (100, 100));
+    }
+}
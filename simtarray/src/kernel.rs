@@ -0,0 +1,97 @@
+//! Support for the code generated by [`simt_kernel`](crate::simt_kernel).
+
+use mdarray::{Dense, Shape};
+
+use crate::{DeviceSlice, Init, SimtArray};
+
+#[cfg(target_arch = "amdgpu")]
+pub use crate::amdgpu::Grid;
+#[cfg(all(
+    any(test, feature = "host"),
+    not(any(target_arch = "nvptx64", target_arch = "amdgpu"))
+))]
+pub use crate::host::Grid;
+#[cfg(target_arch = "nvptx64")]
+pub use crate::nvptx::Grid;
+
+/// Converts a dimension given in `#[shape(..)]`.
+#[inline]
+pub fn dim<N: TryInto<usize>>(n: N) -> usize {
+    match n.try_into() {
+        Ok(n) => n,
+        Err(_) => panic!("shape dimension does not fit in usize"),
+    }
+}
+
+/// Turns a `#[grid]` parameter into an array.
+#[inline]
+pub fn grid_array<'s, T, Sh: Shape>(
+    name: &str,
+    slice: DeviceSlice<'s, T>,
+    shape: Sh,
+) -> SimtArray<'s, T, Grid, Init, Dense, Sh> {
+    match SimtArray::from_device_slice(slice, shape) {
+        Ok(array) => array,
+        Err(error) => panic!("parameter `{name}`: {error}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec;
+
+    use crate::{
+        Blocked, Cyclic, DeviceSlice, Projection,
+        host::{Block, Buffer, Grid, Thread, Xy, Xyz, Xz, Y, launch},
+        simt_kernel,
+    };
+
+    /// Adds `block * 100 + offset` to every element of the tile of a block.
+    #[simt_kernel]
+    fn add_block_idx(
+        #[grid]
+        #[shape(rows, 6)]
+        data: &mut [u32],
+        rows: u32,
+        offset: u32,
+    ) {
+        let block = <Xy as Projection<Block, Grid>>::idx();
+        let mut tile = data.split::<Block, (Xz, Y), Blocked>().unwrap();
+        let mut part = unsafe { tile.view_mut::<Thread, (Xz, Y), Cyclic>() };
+        let mut view = part.expr_mut().unwrap();
+        for i in 0..view.dim(0) {
+            for j in 0..view.dim(1) {
+                view[[i, j]] += block * 100 + offset;
+            }
+        }
+    }
+
+    /// Adds one to every element of a rank 1 array of the slice's length.
+    #[simt_kernel]
+    fn increment(#[grid] data: &mut [u32]) {
+        let mut tile = data.split::<Block, (Xyz,), Blocked>().unwrap();
+        let mut part = unsafe { tile.view_mut::<Thread, (Xyz,), Cyclic>() };
+        let mut view = part.expr_mut().unwrap();
+        for i in 0..view.dim(0) {
+            view[[i]] += 1;
+        }
+    }
+
+    #[test]
+    fn simt_kernel() {
+        let data = Buffer::new(vec![1u32; 8 * 6]);
+        launch((2, 2), (2, 3), || unsafe {
+            add_block_idx(DeviceSlice::from_raw_parts(data.ptr(), 48), 8, 5);
+        });
+        for (i, row) in data.into_vec().chunks(6).enumerate() {
+            for (j, &value) in row.iter().enumerate() {
+                assert_eq!(value, (i / 4 + j / 3 * 2) as u32 * 100 + 6);
+            }
+        }
+        let data = Buffer::new(vec![1u32; 12]);
+        launch(2, 3, || unsafe {
+            increment(DeviceSlice::from_raw_parts(data.ptr(), 12));
+        });
+        assert_eq!(data.into_vec(), [2; 12]);
+    }
+}
@@ -1,12 +1,33 @@
 #![no_std]
-#![cfg_attr(target_arch = "nvptx64", feature(stdarch_nvptx))]
+#![cfg_attr(
+    target_arch = "nvptx64",
+    feature(stdarch_nvptx, asm_experimental_arch, f16)
+)]
+#![cfg_attr(
+    target_arch = "amdgpu",
+    feature(stdarch_amdgpu, core_intrinsics, gpu_intrinsics),
+    allow(internal_features)
+)]
 
-pub struct SimtArray<T, Sc, I, L: Layout, Sh: Shape> {
+// Only the host emulation needs `std`
+#[cfg(all(
+    any(test, feature = "host"),
+    not(any(target_arch = "nvptx64", target_arch = "amdgpu"))
+))]
+extern crate std;
+
+// Lets `#[simt_kernel]` refer to `::simtarray` inside this crate as well
+extern crate self as simtarray;
+
+/// An array shared by all units of scope `Sc`. It borrows the memory it was
+/// created from for `'s`.
+pub struct SimtArray<'s, T, Sc, I, L: Layout, Sh: Shape> {
     ptr: *mut T,
     layout: PhantomData<L>,
     mapping: L::Mapping<Sh>,
     scope: PhantomData<Sc>,
     state: PhantomData<I>,
+    storage: PhantomData<&'s mut [T]>,
 }
 
 pub struct SimtArrayMutRef<'a, T, Sc: SyncableScope, L: Layout, Sh: Shape> {
@@ -32,12 +53,45 @@ impl<'a, T, Sc: SyncableScope, L: Layout, Sh: Shape> Drop for SimtArrayMutRef<'a
     }
 }
 
-impl<T, Sc, I, L: Layout, Sh: Shape> SimtArray<T, Sc, I, L, Sh>
+/// The part of an array a unit wrote with `write_once`, together with the
+/// whole array it belongs to.
+pub struct Split<'s, T, Sc, L: Layout, Sh: Shape, P: Shape> {
+    part: Option<SimtArray<'s, T, Sc, FinallySplit, Strided, P>>,
+    parent: SimtArray<'s, T, Sc, Init, L, Sh>,
+}
+
+impl<'s, T, Sc: Scope, L: Layout, Sh: Shape, P: Shape> Split<'s, T, Sc, L, Sh, P> {
+    /// The written part, or `None` if the unit did not get any element
+    pub fn part(&self) -> Option<&SimtArray<'s, T, Sc, FinallySplit, Strided, P>> {
+        self.part.as_ref()
+    }
+    /// Gives up the whole array and keeps only the written part
+    pub fn into_part(self) -> Option<SimtArray<'s, T, Sc, FinallySplit, Strided, P>> {
+        self.part
+    }
+    /// Synchronizes the scope, after which every unit wrote its part, and
+    /// returns the whole array, e.g. to split it again with another distribution.
+    ///
+    /// # Safety
+    /// Must be called in a kernel uniform control flow state
+    pub unsafe fn join(self) -> SimtArray<'s, T, Sc, Init, L, Sh>
+    where
+        Sc: SyncableScope,
+    {
+        unsafe { Sc::sync() };
+        self.parent
+    }
+}
+
+impl<'s, T, Sc, I, L: Layout, Sh: Shape> SimtArray<'s, T, Sc, I, L, Sh>
 where
     Sc: Scope,
     I: State,
 {
     /// # Safety
+    /// `ptr` must point to memory that holds the array described by `mapping`
+    /// and stays valid for `'s`. `'s` is not tied to anything, so during it the
+    /// memory must only be accessed through this array.
     pub unsafe fn new_unchecked(ptr: *mut T, mapping: L::Mapping<Sh>) -> Self {
         Self {
             ptr,
@@ -45,11 +99,12 @@ where
             layout: PhantomData,
             scope: PhantomData,
             state: PhantomData,
+            storage: PhantomData,
         }
     }
 }
 
-impl<T, Sc, I: Splitable, L: Layout, Sh: Shape> SimtArray<T, Sc, I, L, Sh>
+impl<'s, T, Sc, I: Splitable, L: Layout, Sh: Shape> SimtArray<'s, T, Sc, I, L, Sh>
 where
     Sc: Scope,
     I: Viewable,
@@ -63,229 +118,428 @@ where
 }
 type Unsigned<Sc> = <<<Sc as Scope>::Arch as Arch>::IndexSize as SizeType>::Unsigned;
 
-impl<T, Sc, I: Splitable + Viewable, L: Layout, D0: Dim> SimtArray<T, Sc, I, L, (D0,)>
-where
-    Sc: Scope,
-{
-    pub fn view_with_limited_quantity<'a, E, Ps>(
-        &'a self,
-        quantity: Unsigned<Sc>,
-    ) -> Option<View<'a, T, (usize,), Strided>>
-    where
-        E: Scope<Arch = Sc::Arch>,
-        Ps: ProjectionSetDim0<(D0,), E, Sc, Arch = Sc::Arch>,
-    {
-        let dim0 = Ps::dim0();
-        let idx0 = Ps::idx0();
-        if idx0.as_() >= self.mapping.dim(0) {
-            return None;
+/// Implements the splitting methods for a `SimtArray` of a given rank.
+///
+/// Each dimension is listed as `Dim index dimK idxK iK qK mK`, where `dimK` and
+/// `idxK` are the accessors of the `ProjectionSetDimK` trait given first.
+/// Quantities are passed as a tuple for higher ranks. A single dimension keeps
+/// the part shape of its distribution, higher ranks require one dimensional parts.
+macro_rules! impl_split {
+    // Rank 1 takes a plain index/quantity, higher ranks take a tuple.
+    (@pat $i:ident) => { $i };
+    (@pat $($i:ident),+) => { ($($i),+) };
+    (@quantity $q:ident) => { Unsigned<Sc> };
+    (@quantity $($q:ident),+) => { ($(impl_split!(@unsigned $q)),+) };
+    (@unsigned $q:ident) => { Unsigned<Sc> };
+    (@usize $i:ident) => { usize };
+    (@index $i:ident) => { GlobalIndex };
+    (@mapping $m:ident) => { $m };
+    (@mapping $($m:ident),+) => {
+        StridedMapping::new(($($m.dim(0)),+), &[$($m.stride(0)),+])
+    };
+    (@for () $body:block) => { $body };
+    (@for ($i:ident in $it:expr $(, $ri:ident in $rit:expr)*) $body:block) => {
+        for $i in $it {
+            impl_split!(@for ($($ri in $rit),*) $body)
         }
-        if idx0 >= quantity {
-            return None;
+    };
+    (
+        $Ps:ident,
+        ($($D:ident $k:tt $dim:ident $idx:ident $i:ident $q:ident $m:ident),+),
+        $out:ty,
+        $($Di:tt)+
+    ) => {
+        impl<'s, T, Sc, I, L: Layout, $($D: Dim),+> SimtArray<'s, T, Sc, I, L, ($($D),+,)>
+        where
+            Sc: Scope,
+        {
+            /// Returns the offset and the mapping of the part of unit `idxK` out
+            /// of `dimK` units along each dimension.
+            #[inline]
+            fn part<Di>(
+                &self,
+                ($($idx,)+): ($(impl_split!(@usize $idx),)+),
+                ($($dim,)+): ($(impl_split!(@usize $dim),)+),
+            ) -> Option<(isize, StridedMapping<$out>)>
+            where
+                Di: $($Di)+ $(+ Distributes<$D>)+,
+            {
+                $(let () = <Di as Distributes<$D>>::CHECK;)+
+                let mut offset = 0;
+                $(
+                    let $m = {
+                        let stride = self.mapping.stride($k);
+                        let len = self.mapping.dim($k);
+                        let (start, mapping) = Di::part(len, stride, $idx, $dim)?;
+                        offset += start;
+                        mapping
+                    };
+                )+
+                Some((offset, impl_split!(@mapping $($m),+)))
+            }
+            /// Like [`part`](Self::part), but only the first `quantity` units along
+            /// each dimension receive a part of the array.
+            #[inline]
+            fn limited_part<E, Ps, Di>(
+                &self,
+                quantity: impl_split!(@quantity $($q),+),
+            ) -> Option<(isize, StridedMapping<$out>)>
+            where
+                E: Scope<Arch = Sc::Arch>,
+                Ps: $Ps<($($D),+,), E, Sc, Arch = Sc::Arch>,
+                Di: $($Di)+ $(+ Distributes<$D>)+,
+            {
+                let impl_split!(@pat $($q),+) = quantity;
+                $(
+                    let $idx = Ps::$idx();
+                    if $idx >= $q {
+                        return None;
+                    }
+                    let $dim = Ps::$dim().min($q);
+                )+
+                self.part::<Di>(($($idx.as_(),)+), ($($dim.as_(),)+))
+            }
         }
-        let quantity = dim0.min(quantity);
 
-        unsafe {
-            Some(
-                View::<_, (D0,), L>::new_unchecked(self.ptr, self.mapping.clone()).into_view(
-                    StepRange {
-                        range: idx0.as_()..,
-                        step: quantity.as_() as isize,
-                    },
-                ),
-            )
-        }
-    }
-    pub fn view_mut_with_limited_quantity<'a, E, Ps>(
-        &'a mut self,
-        quantity: Unsigned<Sc>,
-    ) -> SimtArrayMutRef<'a, T, Sc, Strided, (usize,)>
-    where
-        Sc: SyncableScope,
-        E: UnitScope<Arch = Sc::Arch>,
-        Ps: ProjectionSetDim0<(D0,), E, Sc, Arch = Sc::Arch>,
-    {
-        let dim0 = Ps::dim0();
-        let idx0 = Ps::idx0();
-        if idx0.as_() >= self.mapping.dim(0) || idx0 >= quantity {
-            return SimtArrayMutRef {
-                view: None,
-                scope: PhantomData,
-                layout: PhantomData,
-            };
-        }
-        let quantity = dim0.min(quantity);
-        let view_mut = unsafe {
-            ViewMut::<_, (D0,), L>::new_unchecked(self.ptr, self.mapping.clone()).into_view(
-                StepRange {
-                    range: idx0.as_()..,
-                    step: quantity.as_() as isize,
-                },
-            )
-        };
-        SimtArrayMutRef {
-            view: Some(view_mut.into_raw_parts()),
-            layout: PhantomData,
-            scope: PhantomData,
-        }
-    }
-    pub fn view<'a, E, Ps>(&'a self) -> Option<View<'a, T, (usize,), Strided>>
-    where
-        E: Scope<Arch = Sc::Arch>,
-        Ps: ProjectionSetDim0<(D0,), E, Sc, Arch = Sc::Arch>,
-    {
-        let dim0 = Ps::dim0();
-        let idx0 = Ps::idx0();
-        if idx0.as_() >= self.mapping.dim(0) {
-            return None;
+        impl<'s, T, Sc, I: Splitable + Viewable, L: Layout, $($D: Dim),+> SimtArray<'s, T, Sc, I, L, ($($D),+,)>
+        where
+            Sc: Scope,
+        {
+            /// Like [`view`](Self::view), but only the first `quantity` units along
+            /// each dimension receive a part of the array.
+            pub fn view_with_limited_quantity<'a, E, Ps, Di>(
+                &'a self,
+                quantity: impl_split!(@quantity $($q),+),
+            ) -> Option<View<'a, T, $out, Strided>>
+            where
+                E: Scope<Arch = Sc::Arch>,
+                Ps: $Ps<($($D),+,), E, Sc, Arch = Sc::Arch>,
+                Di: $($Di)+ $(+ Distributes<$D>)+,
+            {
+                let (offset, mapping) = self.limited_part::<E, Ps, Di>(quantity)?;
+                unsafe { Some(View::new_unchecked(self.ptr.offset(offset), mapping)) }
+            }
+            /// Like [`view_mut`](Self::view_mut), but only the first `quantity` units
+            /// along each dimension receive a part of the array.
+            ///
+            /// # Safety
+            /// See [`view_mut`](Self::view_mut).
+            pub unsafe fn view_mut_with_limited_quantity<'a, E, Ps, Di>(
+                &'a mut self,
+                quantity: impl_split!(@quantity $($q),+),
+            ) -> SimtArrayMutRef<'a, T, Sc, Strided, $out>
+            where
+                Sc: SyncableScope,
+                E: UnitScope<Arch = Sc::Arch>,
+                Ps: $Ps<($($D),+,), E, Sc, Arch = Sc::Arch>,
+                Di: $($Di)+ $(+ Distributes<$D>)+,
+            {
+                let view = self.limited_part::<E, Ps, Di>(quantity);
+                SimtArrayMutRef {
+                    view: view.map(|(offset, mapping)| {
+                        (unsafe { self.ptr.offset(offset) }, mapping)
+                    }),
+                    layout: PhantomData,
+                    scope: PhantomData,
+                }
+            }
+            /// Returns the part of the array that belongs to the calling unit of
+            /// scope `E` under the distribution `Di`, or `None` if it does not get
+            /// any element.
+            pub fn view<'a, E, Ps, Di>(&'a self) -> Option<View<'a, T, $out, Strided>>
+            where
+                E: Scope<Arch = Sc::Arch>,
+                Ps: $Ps<($($D),+,), E, Sc, Arch = Sc::Arch>,
+                Di: $($Di)+ $(+ Distributes<$D>)+,
+            {
+                let idx = ($(Ps::$idx().as_(),)+);
+                let units = ($(Ps::$dim().as_(),)+);
+                let (offset, mapping) = self.part::<Di>(idx, units)?;
+                unsafe { Some(View::new_unchecked(self.ptr.offset(offset), mapping)) }
+            }
+            /// Returns the part of the array that belongs to the calling thread under
+            /// the distribution `Di`. The scope is synchronized when the returned
+            /// reference is dropped.
+            ///
+            /// # Safety
+            /// Must be called in a kernel uniform control flow state. The returned
+            /// reference must be dropped in one as well and must not be leaked, as
+            /// other units may only read the array after it was dropped.
+            pub unsafe fn view_mut<'a, 'b, 'c, E, Ps, Di>(
+                &'a mut self,
+            ) -> SimtArrayMutRef<'a, T, Sc, Strided, $out>
+            where
+                'a: 'b,
+                'b: 'c,
+                Sc: SyncableScope,
+                E: UnitScope<Arch = Sc::Arch>,
+                Ps: $Ps<($($D),+,), E, Sc, Arch = Sc::Arch>,
+                Di: $($Di)+ $(+ Distributes<$D>)+,
+            {
+                let idx = ($(Ps::$idx().as_(),)+);
+                let units = ($(Ps::$dim().as_(),)+);
+                let view = self.part::<Di>(idx, units);
+                SimtArrayMutRef {
+                    view: view.map(|(offset, mapping)| {
+                        (unsafe { self.ptr.offset(offset) }, mapping)
+                    }),
+                    layout: PhantomData,
+                    scope: PhantomData,
+                }
+            }
         }
 
-        unsafe {
-            Some(
-                View::<_, (D0,), L>::new_unchecked(self.ptr, self.mapping.clone()).into_view(
-                    StepRange {
-                        range: idx0.as_()..,
-                        step: dim0.as_() as isize,
+        impl<'s, T, Sc, I, L: Layout, $($D: Dim),+> SimtArray<'s, T, Sc, I, L, ($($D),+,)>
+        where
+            Sc: Scope,
+            I: Splitable,
+        {
+            /// Returns the part of the array that belongs to the calling unit of
+            /// scope `E` under the distribution `Di` as an array of that scope, or
+            /// `None` if it does not get any element. The part keeps the state of
+            /// the array and can be split again, e.g. a `Grid` array into `Block`
+            /// tiles and those into the parts of their threads.
+            pub fn split<E, Ps, Di>(self) -> Option<SimtArray<'s, T, E, I, Strided, $out>>
+            where
+                E: Scope<Arch = Sc::Arch>,
+                Ps: $Ps<($($D),+,), E, Sc, Arch = Sc::Arch>,
+                Di: $($Di)+ $(+ Distributes<$D>)+,
+            {
+                let idx = ($(Ps::$idx().as_(),)+);
+                let units = ($(Ps::$dim().as_(),)+);
+                let (offset, mapping) = self.part::<Di>(idx, units)?;
+                Some(SimtArray {
+                    ptr: unsafe { self.ptr.offset(offset) },
+                    layout: PhantomData,
+                    mapping,
+                    scope: PhantomData,
+                    state: PhantomData,
+                    storage: PhantomData,
+                })
+            }
+            /// Writes every element of the part the calling unit gets under the
+            /// distribution `Di`. `f` receives the index of the element in the whole
+            /// array as a [`GlobalIndex`] per dimension, not its position in the
+            /// part of the unit.
+            /// The returned handle gives access to the written part and joins the
+            /// whole array back once every unit wrote its part.
+            ///
+            /// # Safety
+            /// Must be called in a kernel uniform control flow state
+            pub unsafe fn write_once<E, Ps, Di, F>(
+                self,
+                f: F,
+            ) -> Split<'s, T, Sc, L, ($($D),+,), $out>
+            where
+                E: UnitScope<Arch = Sc::Arch>,
+                Ps: $Ps<($($D),+,), E, Sc, Arch = Sc::Arch>,
+                Di: $($Di)+ $(+ Distributes<$D>)+,
+                F: FnMut(($(impl_split!(@index $i)),+,)) -> T,
+            {
+                let part = self.write_once_inner::<_, Ps, Di, _>(f);
+                Split {
+                    part,
+                    parent: SimtArray {
+                        ptr: self.ptr,
+                        layout: PhantomData,
+                        mapping: self.mapping,
+                        scope: PhantomData,
+                        state: PhantomData,
+                        storage: PhantomData,
                     },
-                ),
-            )
-        }
-    }
-    pub fn view_mut<'a, 'b, 'c, E, Ps>(
-        &'a mut self,
-    ) -> SimtArrayMutRef<'a, T, Sc, Strided, (usize,)>
-    where
-        'a: 'b,
-        'b: 'c,
-        Sc: SyncableScope,
-        E: UnitScope<Arch = Sc::Arch>,
-        Ps: ProjectionSetDim0<(D0,), E, Sc, Arch = Sc::Arch>,
-    {
-        let dim0 = Ps::dim0();
-        let idx0 = Ps::idx0();
-        if idx0.as_() >= self.mapping.dim(0) {
-            return SimtArrayMutRef {
-                view: None,
-                scope: PhantomData,
-                layout: PhantomData,
-            };
-        }
-        let view_mut = unsafe {
-            ViewMut::<_, (D0,), L>::new_unchecked(self.ptr, self.mapping.clone()).into_view(
-                StepRange {
-                    range: idx0.as_()..,
-                    step: dim0.as_() as isize,
-                },
-            )
-        };
-        SimtArrayMutRef {
-            view: Some(view_mut.into_raw_parts()),
-            layout: PhantomData,
-            scope: PhantomData,
-        }
-    }
-}
+                }
+            }
+            fn write_once_inner<E, Ps, Di, F>(
+                &self,
+                mut f: F,
+            ) -> Option<SimtArray<'s, T, Sc, FinallySplit, Strided, $out>>
+            where
+                E: UnitScope<Arch = Sc::Arch>,
+                Ps: $Ps<($($D),+,), E, Sc, Arch = Sc::Arch>,
+                Di: $($Di)+ $(+ Distributes<$D>)+,
+                F: FnMut(($(impl_split!(@index $i)),+,)) -> T,
+            {
+                $(
+                    let $dim = Ps::$dim().as_();
+                    let $idx = Ps::$idx().as_();
+                )+
+                let (offset, mapping) = self.part::<Di>(($($idx,)+), ($($dim,)+))?;
+                impl_split!(@for ($($i in Di::indices(self.mapping.dim($k), $idx, $dim)),+) {
+                    let offset = 0 $(+ $i as isize * self.mapping.stride($k))+;
+                    unsafe { self.ptr.offset(offset).write(f(($(GlobalIndex($i)),+,))) };
+                });
+                Some(SimtArray {
+                    ptr: unsafe { self.ptr.offset(offset) },
+                    scope: PhantomData,
+                    state: PhantomData,
+                    storage: PhantomData,
+                    layout: PhantomData,
+                    mapping,
+                })
+            }
 
-impl<T, Sc, I, L: Layout, D0: Dim> SimtArray<T, Sc, I, L, (D0,)>
-where
-    Sc: Scope,
-    I: Splitable,
-{
-    /// # Safety
-    /// Must be called in a kernel uniform control flow state
-    pub unsafe fn write_once<E, Ps, F>(
-        self,
-        f: F,
-    ) -> Option<SimtArray<T, Sc, FinallySplit, Strided, (usize,)>>
-    where
-        E: UnitScope<Arch = Sc::Arch>,
-        Ps: ProjectionSetDim0<(D0,), E, Sc, Arch = Sc::Arch>,
-        F: FnMut((D0,)) -> T,
-    {
-        unsafe { self.write_once_inner::<_, Ps, _>(f) }
-    }
-    /// # Safety
-    /// Must be called in a kernel uniform control flow state
-    unsafe fn write_once_inner<E, Ps, F>(
-        self,
-        mut f: F,
-    ) -> Option<SimtArray<T, Sc, FinallySplit, Strided, (usize,)>>
-    where
-        E: UnitScope<Arch = Sc::Arch>,
-        Ps: ProjectionSetDim0<(D0,), E, Sc, Arch = Sc::Arch>,
-        F: FnMut((D0,)) -> T,
-    {
-        let dim0 = Ps::dim0();
-        let idx0 = Ps::idx0();
-        if idx0.as_() >= self.mapping.dim(0) {
-            return None;
-        }
-        let view_parts = unsafe {
-            View::<_, (D0,), L>::new_unchecked(
-                self.ptr as *const UnsafeCell<MaybeUninit<T>>,
-                self.mapping,
-            )
-        }
-        .into_view(StepRange {
-            range: idx0.as_()..,
-            step: dim0.as_() as isize,
-        })
-        .into_raw_parts();
-        let view = unsafe {
-            ViewMut::<_, _, Strided>::new_unchecked(
-                view_parts.0 as *mut MaybeUninit<T>,
-                view_parts.1,
-            )
-        };
-        for_each(enumerate(view), |(idx, item)| {
-            item.write(f((D0::from_size(idx),)));
-        });
-        Some(SimtArray {
-            ptr: view_parts.0 as *mut T,
-            scope: PhantomData,
-            state: PhantomData,
-            layout: PhantomData,
-            mapping: view_parts.1,
-        })
-    }
-
-    /// # Safety
-    /// Must be called in a kernel uniform control flow state
-    pub unsafe fn init_with<E, Ps, F>(self, f: F) -> SimtArray<T, Sc, Init, L, (D0,)>
-    where
-        E: UnitScope<Arch = Sc::Arch>,
-        Ps: ProjectionSetDim0<(D0,), E, Sc, Arch = Sc::Arch>,
-        F: FnMut((D0,)) -> T,
-        Sc: SyncableScope,
-    {
-        let out = SimtArray {
-            ptr: self.ptr,
-            layout: PhantomData,
-            mapping: self.mapping.clone(),
-            scope: PhantomData,
-            state: PhantomData,
-        };
-        unsafe {
-            self.write_once_inner::<_, Ps, _>(f);
-            <Sc as SyncableScope>::sync();
+            /// Writes every element of the array, each unit of scope `E` the part
+            /// it gets under the distribution `Di`, and synchronizes the scope.
+            /// `f` receives the index of the element in the whole array, like for
+            /// [`write_once`](Self::write_once).
+            ///
+            /// # Safety
+            /// Must be called in a kernel uniform control flow state
+            pub unsafe fn init_with<E, Ps, Di, F>(
+                self,
+                f: F,
+            ) -> SimtArray<'s, T, Sc, Init, L, ($($D),+,)>
+            where
+                E: UnitScope<Arch = Sc::Arch>,
+                Ps: $Ps<($($D),+,), E, Sc, Arch = Sc::Arch>,
+                Di: $($Di)+ $(+ Distributes<$D>)+,
+                F: FnMut(($(impl_split!(@index $i)),+,)) -> T,
+                Sc: SyncableScope,
+            {
+                unsafe { self.write_once::<E, Ps, Di, F>(f).join() }
+            }
         }
-        out
-    }
+    };
 }
 
+impl_split!(ProjectionSetDim0, (D0 0 dim0 idx0 i0 q0 m0), Di::Part, Distribution);
+impl_split!(
+    ProjectionSetDim1,
+    (D0 0 dim0 idx0 i0 q0 m0, D1 1 dim1 idx1 i1 q1 m1),
+    (usize, usize),
+    Distribution<Part = (usize,)>
+);
+impl_split!(
+    ProjectionSetDim2,
+    (D0 0 dim0 idx0 i0 q0 m0, D1 1 dim1 idx1 i1 q1 m1, D2 2 dim2 idx2 i2 q2 m2),
+    (usize, usize, usize),
+    Distribution<Part = (usize,)>
+);
+
 mod archs;
+mod atomic;
+mod bf16;
+mod copy_async;
+mod device_slice;
+mod distribution;
 mod init_state;
-
+// Needs the `Grid` of an architecture
+#[cfg(any(
+    target_arch = "nvptx64",
+    target_arch = "amdgpu",
+    test,
+    feature = "host"
+))]
+#[doc(hidden)]
+pub mod kernel;
+mod pipeline;
+mod reduce;
+mod scan;
+mod shared_memory;
 mod size_type;
+mod uniform;
 
-use core::{cell::UnsafeCell, marker::PhantomData, mem::MaybeUninit};
+use core::marker::PhantomData;
 
 pub use archs::*;
+pub use atomic::*;
+pub use bf16::*;
+pub use copy_async::*;
+pub use device_slice::*;
+pub use distribution::*;
 pub use init_state::*;
-use mdarray::{
-    Dim, Layout, Mapping, Shape, StepRange, Strided, View, ViewMut,
-    expr::{enumerate, for_each},
-};
+pub use mdarray;
+use mdarray::{Dim, Layout, Mapping, Shape, Strided, StridedMapping, View, ViewMut};
+pub use pipeline::*;
+pub use shared_memory::*;
+pub use simtarray_derive::simt_kernel;
 pub use size_type::*;
+pub use uniform::*;
 
 pub(crate) mod util;
+
+#[cfg(test)]
+mod tests {
+    use std::vec;
+
+    use mdarray::Mapping;
+
+    use super::*;
+    use crate::host::{Block, Buffer, Grid, Thread, X, Xy, Xyz, Xz, Y, launch};
+
+    #[test]
+    fn init_with_and_view() {
+        let data = Buffer::new(vec![0u32; 10]);
+        launch(1, 4, || {
+            let array: SimtArray<u32, Block, Init, _, _> = unsafe {
+                data.array::<Block, Uninit, _>(0, (10,))
+                    .init_with::<Thread, (Xyz,), Cyclic, _>(|(GlobalIndex(i),)| i as u32 + 1)
+            };
+            let idx = <Xyz as Projection<Thread, Block>>::idx() as usize;
+            let view = array.view::<Thread, (Xyz,), Cyclic>().unwrap();
+            assert_eq!(view.len(), (10 - idx).div_ceil(4));
+            assert_eq!(view[[0]], idx as u32 + 1);
+        });
+    }
+
+    #[test]
+    fn split() {
+        let data = Buffer::new(vec![0u32; 8 * 6]);
+        launch((2, 2), (2, 3), || {
+            let array = data.array::<Grid, Uninit, _>(0, (8, 6));
+            let block = <Xy as Projection<Block, Grid>>::idx();
+            let tile = array.split::<Block, (Xz, Y), Blocked>().unwrap();
+            assert_eq!((tile.mapping.dim(0), tile.mapping.dim(1)), (4, 3));
+            let tile = unsafe {
+                tile.init_with::<Thread, (Xz, Y), Cyclic, _>(|(GlobalIndex(i), GlobalIndex(j))| {
+                    block * 100 + (i * 3 + j) as u32
+                })
+            };
+            assert_eq!(tile.expr()[[3, 2]], block * 100 + 11);
+        });
+        for (i, row) in data.into_vec().chunks(6).enumerate() {
+            for (j, &value) in row.iter().enumerate() {
+                let block = (i / 4 + j / 3 * 2) as u32;
+                assert_eq!(value, block * 100 + (i % 4 * 3 + j % 3) as u32);
+            }
+        }
+    }
+
+    #[test]
+    fn write_once_and_join() {
+        let data = Buffer::new(vec![0u32; 8]);
+        launch(1, 4, || {
+            let split = unsafe {
+                data.array::<Block, Uninit, _>(0, (8,))
+                    .write_once::<Thread, (Xyz,), Cyclic, _>(|(GlobalIndex(i),)| i as u32 * 10)
+            };
+            assert_eq!(split.part().unwrap().mapping.dim(0), 2);
+            let array = unsafe { split.join() };
+            let idx = <Xyz as Projection<Thread, Block>>::idx();
+            let view = array.view::<Thread, (Xyz,), Blocked>().unwrap();
+            assert_eq!((view[[0]], view[[1]]), (idx * 20, idx * 20 + 10));
+        });
+    }
+
+    #[test]
+    fn view_mut_2d() {
+        let data = Buffer::new(vec![0u32; 6 * 4]);
+        launch(1, (3, 2), || {
+            let mut array = data.array::<Block, Init, _>(0, (6, 4));
+            let x = <X as Projection<Thread, Block>>::idx();
+            let y = <Y as Projection<Thread, Block>>::idx();
+            let mut part = unsafe { array.view_mut::<Thread, (Xz, Y), Cyclic>() };
+            let mut view = part.expr_mut().unwrap();
+            for i in 0..view.dim(0) {
+                for j in 0..view.dim(1) {
+                    view[[i, j]] = x * 10 + y;
+                }
+            }
+        });
+        for (i, row) in data.into_vec().chunks(4).enumerate() {
+            for (j, &value) in row.iter().enumerate() {
+                assert_eq!(value, (i % 3) as u32 * 10 + (j % 2) as u32);
+            }
+        }
+    }
+}
@@ -0,0 +1,223 @@
+use core::{marker::PhantomData, ptr};
+
+use mdarray::{Layout, Shape};
+
+use crate::{
+    AsyncCopies, AsyncCopyScope, Init, ProjectionSetDim0, Scope, SimtArray, Uninit, UnitScope,
+    Viewable,
+};
+
+/// Rotates through `STAGES` arrays of scope `Sc`, e.g. shared memory tiles of
+/// a block, so that the next stages are filled while the current one is used.
+///
+/// Stages are produced and consumed in order. Producing a stage commits one
+/// group of async copies, consuming it waits for that group and synchronizes
+/// the scope before and after the stage is used, so that nobody still reads it
+/// when it is produced the next time. The pipeline remembers the group of every
+/// stage, so other async copies may be committed in between through
+/// [`copies`](Self::copies).
+///
+/// ```ignore
+/// let stages = [shared_array!(shared; f32; 64, 64), shared_array!(shared; f32; 64, 64)];
+/// // The sources are read until `run` returns
+/// let tiles: [_; N] = core::array::from_fn(tile_of);
+/// Pipeline::run(&mut copies, stages, |pipeline| {
+///     pipeline.copy_from_async::<Thread, (Xyz,), _, _, _>(&tiles[0]);
+///     for k in 1..=N {
+///         unsafe { pipeline.consume(|stage| { /* ... use stage */ }) };
+///         if k < N {
+///             pipeline.copy_from_async::<Thread, (Xyz,), _, _, _>(&tiles[k]);
+///         }
+///     }
+/// });
+/// ```
+pub struct Pipeline<'a, 's, T, Sc: AsyncCopyScope, L: Layout, Sh: Shape, const STAGES: usize> {
+    stages: [SimtArray<'s, T, Sc, Uninit, L, Sh>; STAGES],
+    /// Group of the copies into each stage
+    groups: [usize; STAGES],
+    copies: &'a mut AsyncCopies<Sc>,
+    /// Stage the next produce fills
+    next: usize,
+    /// Produced stages that were not consumed yet
+    pending: usize,
+    /// Copies read their sources until the end of [`run`](Self::run)
+    sources: PhantomData<&'a T>,
+}
+
+impl<'a, 's, T, Sc, L, Sh, const STAGES: usize> Pipeline<'a, 's, T, Sc, L, Sh, STAGES>
+where
+    T: Copy,
+    Sc: AsyncCopyScope,
+    L: Layout,
+    Sh: Shape,
+{
+    /// Runs `f` with a pipeline over `stages` and waits for all copies of the
+    /// calling unit afterwards, so that none of them outlives the borrows of the
+    /// stages or the sources.
+    pub fn run<R>(
+        copies: &'a mut AsyncCopies<Sc>,
+        stages: [SimtArray<'s, T, Sc, Uninit, L, Sh>; STAGES],
+        f: impl FnOnce(&mut Self) -> R,
+    ) -> R {
+        const { assert!(STAGES > 0, "a pipeline needs at least one stage") };
+        let mut pipeline = Self {
+            stages,
+            groups: [0; STAGES],
+            copies,
+            next: 0,
+            pending: 0,
+            sources: PhantomData,
+        };
+        let result = f(&mut pipeline);
+        Sc::wait_group::<0>();
+        result
+    }
+
+    /// Number of stages that were produced but not consumed yet
+    pub fn pending(&self) -> usize {
+        self.pending
+    }
+
+    /// The async copies of the calling unit, e.g. to copy into other arrays
+    /// between the stages
+    pub fn copies(&mut self) -> &mut AsyncCopies<Sc> {
+        self.copies
+    }
+
+    /// Starts copying `src` into the next stage, see
+    /// [`SimtArray::copy_from_async`].
+    ///
+    /// # Panics
+    /// Panics if every stage is pending or the shapes differ.
+    pub fn copy_from_async<E, Ps, So, I, Ls>(&mut self, src: &'a SimtArray<'_, T, So, I, Ls, Sh>)
+    where
+        E: UnitScope<Arch = Sc::Arch>,
+        Ps: ProjectionSetDim0<(usize,), E, Sc, Arch = Sc::Arch>,
+        So: Scope<Arch = Sc::Arch>,
+        I: Viewable,
+        Ls: Layout,
+    {
+        let idx = self.claim();
+        self.groups[idx] = self.stages[idx].issue_copy::<E, Ps, So, I, Ls>(self.copies, src);
+    }
+
+    /// Fills the next stage with `f`, e.g. through `init_with`, which must
+    /// return the array it was given after initializing it.
+    ///
+    /// # Panics
+    /// Panics if every stage is pending or `f` returns another array.
+    pub fn produce_with<F>(&mut self, f: F)
+    where
+        F: FnOnce(SimtArray<'s, T, Sc, Uninit, L, Sh>) -> SimtArray<'s, T, Sc, Init, L, Sh>,
+    {
+        let idx = self.claim();
+        let stage = &self.stages[idx];
+        let array = unsafe { SimtArray::new_unchecked(stage.ptr, stage.mapping.clone()) };
+        let array = f(array);
+        assert!(
+            ptr::eq(array.ptr, stage.ptr),
+            "stage was replaced by another array"
+        );
+        // Keeps one group per stage, so that consuming waits for the right one
+        self.groups[idx] = self.copies.commit();
+    }
+
+    /// Waits until the oldest pending stage is complete in every unit and
+    /// passes it to `f`. The scope is synchronized again after `f` returned,
+    /// after which the stage can be produced again.
+    ///
+    /// # Panics
+    /// Panics if no stage is pending.
+    ///
+    /// # Safety
+    /// Must be called in a kernel uniform control flow state
+    pub unsafe fn consume<R>(
+        &mut self,
+        f: impl FnOnce(&SimtArray<'s, T, Sc, Init, L, Sh>) -> R,
+    ) -> R {
+        assert!(self.pending > 0, "no stage is pending");
+        let idx = (self.next + STAGES - self.pending) % STAGES;
+        self.pending -= 1;
+        // Younger groups may stay in flight
+        self.copies.wait_for(self.groups[idx]);
+        let stage = &self.stages[idx];
+        unsafe {
+            Sc::sync();
+            let result = f(&SimtArray::new_unchecked(stage.ptr, stage.mapping.clone()));
+            Sc::sync();
+            result
+        }
+    }
+
+    /// Index of the stage to produce next
+    fn claim(&mut self) -> usize {
+        assert!(self.pending < STAGES, "every stage is pending");
+        let idx = self.next;
+        self.next = (self.next + 1) % STAGES;
+        self.pending += 1;
+        idx
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+
+    use super::*;
+    use crate::{
+        Cyclic, GlobalIndex,
+        host::{Block, Buffer, Grid, Thread, Xyz, Xz, Y, launch},
+    };
+
+    #[test]
+    fn pipeline() {
+        let src = Buffer::new((0..30u32).collect::<Vec<_>>());
+        let stages = Buffer::new(std::vec![0u32; 3 * 6]);
+        let other = Buffer::new(std::vec![0u32; 6]);
+        launch(1, (2, 3), || {
+            let mut copies = unsafe { AsyncCopies::new() };
+            let tiles: Vec<_> = (0..5)
+                .map(|k| src.array::<Grid, Init, _>(k * 6, (2, 3)))
+                .collect();
+            let stage = |s: usize| stages.array::<Block, Uninit, _>(s * 6, (2, 3));
+            let stages = [stage(0), stage(1), stage(2)];
+            Pipeline::run(&mut copies, stages, |pipeline| {
+                for tile in &tiles[..2] {
+                    pipeline.copy_from_async::<Thread, (Xyz,), _, _, _>(tile);
+                }
+                // A copy between the stages must not shift their groups
+                let other = unsafe {
+                    other
+                        .array::<Block, Uninit, _>(0, (2, 3))
+                        .copy_from_async::<Thread, (Xyz,), _, _, _>(pipeline.copies(), &tiles[4])
+                        .wait()
+                };
+                assert_eq!(other.expr()[[1, 2]], 29);
+                for k in 0..5 {
+                    let sum = unsafe {
+                        pipeline.consume(|tile| {
+                            let view = tile.expr();
+                            (0..2)
+                                .flat_map(|i| (0..3).map(move |j| (i, j)))
+                                .map(|(i, j)| view[[i, j]])
+                                .sum::<u32>()
+                        })
+                    };
+                    assert_eq!(sum, (k * 6..k * 6 + 6).sum());
+                    match k + 2 {
+                        4 => pipeline.produce_with(|array| unsafe {
+                            array.init_with::<Thread, (Xz, Y), Cyclic, _>(
+                                |(GlobalIndex(i), GlobalIndex(j))| 24 + (i * 3 + j) as u32,
+                            )
+                        }),
+                        n if n < 5 => {
+                            pipeline.copy_from_async::<Thread, (Xyz,), _, _, _>(&tiles[n as usize])
+                        }
+                        _ => {}
+                    }
+                }
+                assert_eq!(pipeline.pending(), 0);
+            });
+        });
+    }
+}
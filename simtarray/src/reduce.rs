@@ -0,0 +1,160 @@
+use core::ops::Add;
+
+use mdarray::{Dense, Dim, Layout, Mapping};
+
+use crate::{
+    AsUsize, Distributes, Init, ProjectionSetDim0, SimtArray, SyncableScope, Uninit, UnitScope,
+    util::combine_options,
+};
+
+impl<'s, T: Copy, Sc: SyncableScope, L: Layout, D0: Dim> SimtArray<'s, T, Sc, Init, L, (D0,)> {
+    /// Combines all elements of the array with `op` and returns the result to
+    /// every unit of scope `E`, or `None` if the array is empty.
+    ///
+    /// Each unit folds the part it gets under the distribution `Di`, then the
+    /// partial results are combined in `scratch`, which needs a slot per unit.
+    /// The order of the combinations is unspecified, so `op` should be
+    /// associative and commutative.
+    ///
+    /// # Panics
+    /// Panics if `scratch` has less elements than there are units of scope `E`.
+    ///
+    /// # Safety
+    /// Must be called in a kernel uniform control flow state
+    pub unsafe fn reduce<E, Ps, Di, S, F>(
+        &self,
+        scratch: &mut SimtArray<'_, Option<T>, Sc, Uninit, Dense, (S,)>,
+        mut op: F,
+    ) -> Option<T>
+    where
+        E: UnitScope<Arch = Sc::Arch>,
+        Ps: ProjectionSetDim0<(D0,), E, Sc, Arch = Sc::Arch>,
+        Di: Distributes<D0>,
+        S: Dim,
+        F: FnMut(T, T) -> T,
+    {
+        let () = <Di as Distributes<D0>>::CHECK;
+        let units: usize = Ps::dim0().as_();
+        let idx: usize = Ps::idx0().as_();
+        assert!(
+            scratch.mapping.dim(0) >= units,
+            "scratch needs a slot per unit"
+        );
+        let stride = self.mapping.stride(0);
+        let partial = Di::indices(self.mapping.dim(0), idx, units)
+            .map(|i| unsafe { self.ptr.offset(i as isize * stride).read() })
+            .reduce(&mut op);
+        let mut combine = combine_options(&mut op);
+        unsafe {
+            scratch.ptr.add(idx).write(partial);
+            Sc::sync();
+            let mut step = 1;
+            while step < units {
+                if idx.is_multiple_of(2 * step) && idx + step < units {
+                    let slot = scratch.ptr.add(idx);
+                    slot.write(combine(slot.read(), scratch.ptr.add(idx + step).read()));
+                }
+                Sc::sync();
+                step *= 2;
+            }
+            let result = scratch.ptr.read();
+            // Nobody may reuse the scratch before every unit has read the result
+            Sc::sync();
+            result
+        }
+    }
+
+    /// Sum of all elements, see [`reduce`](Self::reduce).
+    ///
+    /// # Safety
+    /// Must be called in a kernel uniform control flow state
+    pub unsafe fn sum<E, Ps, Di, S>(
+        &self,
+        scratch: &mut SimtArray<'_, Option<T>, Sc, Uninit, Dense, (S,)>,
+    ) -> Option<T>
+    where
+        E: UnitScope<Arch = Sc::Arch>,
+        Ps: ProjectionSetDim0<(D0,), E, Sc, Arch = Sc::Arch>,
+        Di: Distributes<D0>,
+        S: Dim,
+        T: Add<Output = T>,
+    {
+        unsafe { self.reduce::<E, Ps, Di, S, _>(scratch, |a, b| a + b) }
+    }
+
+    /// Smallest element, see [`reduce`](Self::reduce). Which of several
+    /// equal or unordered elements is returned is unspecified.
+    ///
+    /// # Safety
+    /// Must be called in a kernel uniform control flow state
+    pub unsafe fn min<E, Ps, Di, S>(
+        &self,
+        scratch: &mut SimtArray<'_, Option<T>, Sc, Uninit, Dense, (S,)>,
+    ) -> Option<T>
+    where
+        E: UnitScope<Arch = Sc::Arch>,
+        Ps: ProjectionSetDim0<(D0,), E, Sc, Arch = Sc::Arch>,
+        Di: Distributes<D0>,
+        S: Dim,
+        T: PartialOrd,
+    {
+        unsafe { self.reduce::<E, Ps, Di, S, _>(scratch, |a, b| if b < a { b } else { a }) }
+    }
+
+    /// Largest element, see [`reduce`](Self::reduce). Which of several
+    /// equal or unordered elements is returned is unspecified.
+    ///
+    /// # Safety
+    /// Must be called in a kernel uniform control flow state
+    pub unsafe fn max<E, Ps, Di, S>(
+        &self,
+        scratch: &mut SimtArray<'_, Option<T>, Sc, Uninit, Dense, (S,)>,
+    ) -> Option<T>
+    where
+        E: UnitScope<Arch = Sc::Arch>,
+        Ps: ProjectionSetDim0<(D0,), E, Sc, Arch = Sc::Arch>,
+        Di: Distributes<D0>,
+        S: Dim,
+        T: PartialOrd,
+    {
+        unsafe { self.reduce::<E, Ps, Di, S, _>(scratch, |a, b| if b > a { b } else { a }) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec;
+
+    use crate::{
+        Blocked, Cyclic, GlobalIndex, Uninit,
+        host::{Block, Buffer, Thread, Xyz, launch},
+    };
+
+    #[test]
+    fn reduce() {
+        let data = Buffer::new(vec![0i32; 20]);
+        let scratch = Buffer::new(vec![None::<i32>; 8]);
+        launch(1, 8, || {
+            let mut scratch = scratch.array::<Block, Uninit, _>(0, (8,));
+            unsafe {
+                let array = data
+                    .array::<Block, Uninit, _>(0, (20,))
+                    .init_with::<Thread, (Xyz,), Cyclic, _>(|(GlobalIndex(i),)| i as i32 - 5);
+                assert_eq!(
+                    array.sum::<Thread, (Xyz,), Blocked, _>(&mut scratch),
+                    Some(90)
+                );
+                assert_eq!(
+                    array.min::<Thread, (Xyz,), Cyclic, _>(&mut scratch),
+                    Some(-5)
+                );
+                assert_eq!(
+                    array.max::<Thread, (Xyz,), Blocked, _>(&mut scratch),
+                    Some(14)
+                );
+                let xor = array.reduce::<Thread, (Xyz,), Cyclic, _, _>(&mut scratch, |a, b| a ^ b);
+                assert_eq!(xor, (-5..15).reduce(|a, b| a ^ b));
+            }
+        });
+    }
+}
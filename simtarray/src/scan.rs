@@ -0,0 +1,217 @@
+use core::ops::Add;
+
+use mdarray::{Dense, Dim, Layout, Mapping};
+use num_traits::Zero;
+
+use crate::{
+    AsUsize, Distributes, Init, ProjectionSetDim0, SimtArray, SyncableScope, Uninit, UnitScope,
+    util::combine_options,
+};
+
+impl<'s, T: Copy, Sc: SyncableScope, L: Layout, D0: Dim> SimtArray<'s, T, Sc, Init, L, (D0,)> {
+    /// Replaces every element by `op` applied to all elements up to and including
+    /// it. Each unit of scope `E` updates the part it gets under the distribution
+    /// `Di`, the totals of the units are combined in `scratch`, which needs a slot
+    /// per unit. `op` must be associative.
+    ///
+    /// # Panics
+    /// Panics if `scratch` has less elements than there are units of scope `E`.
+    ///
+    /// # Safety
+    /// Must be called in a kernel uniform control flow state
+    pub unsafe fn inclusive_scan<E, Ps, Di, S, F>(
+        &mut self,
+        scratch: &mut SimtArray<'_, Option<T>, Sc, Uninit, Dense, (S,)>,
+        op: F,
+    ) where
+        E: UnitScope<Arch = Sc::Arch>,
+        Ps: ProjectionSetDim0<(D0,), E, Sc, Arch = Sc::Arch>,
+        Di: Distributes<D0>,
+        S: Dim,
+        F: FnMut(T, T) -> T,
+    {
+        unsafe { self.scan::<E, Ps, Di, S, F>(scratch, None, op) }
+    }
+
+    /// Replaces every element by `op` applied to `init` and all elements before
+    /// it, see [`inclusive_scan`](Self::inclusive_scan).
+    ///
+    /// # Safety
+    /// Must be called in a kernel uniform control flow state
+    pub unsafe fn exclusive_scan<E, Ps, Di, S, F>(
+        &mut self,
+        scratch: &mut SimtArray<'_, Option<T>, Sc, Uninit, Dense, (S,)>,
+        init: T,
+        op: F,
+    ) where
+        E: UnitScope<Arch = Sc::Arch>,
+        Ps: ProjectionSetDim0<(D0,), E, Sc, Arch = Sc::Arch>,
+        Di: Distributes<D0>,
+        S: Dim,
+        F: FnMut(T, T) -> T,
+    {
+        unsafe { self.scan::<E, Ps, Di, S, F>(scratch, Some(init), op) }
+    }
+
+    /// Inclusive prefix sum, see [`inclusive_scan`](Self::inclusive_scan).
+    ///
+    /// # Safety
+    /// Must be called in a kernel uniform control flow state
+    pub unsafe fn inclusive_sum<E, Ps, Di, S>(
+        &mut self,
+        scratch: &mut SimtArray<'_, Option<T>, Sc, Uninit, Dense, (S,)>,
+    ) where
+        E: UnitScope<Arch = Sc::Arch>,
+        Ps: ProjectionSetDim0<(D0,), E, Sc, Arch = Sc::Arch>,
+        Di: Distributes<D0>,
+        S: Dim,
+        T: Add<Output = T>,
+    {
+        unsafe { self.inclusive_scan::<E, Ps, Di, S, _>(scratch, |a, b| a + b) }
+    }
+
+    /// Exclusive prefix sum starting at zero, see
+    /// [`exclusive_scan`](Self::exclusive_scan).
+    ///
+    /// # Safety
+    /// Must be called in a kernel uniform control flow state
+    pub unsafe fn exclusive_sum<E, Ps, Di, S>(
+        &mut self,
+        scratch: &mut SimtArray<'_, Option<T>, Sc, Uninit, Dense, (S,)>,
+    ) where
+        E: UnitScope<Arch = Sc::Arch>,
+        Ps: ProjectionSetDim0<(D0,), E, Sc, Arch = Sc::Arch>,
+        Di: Distributes<D0>,
+        S: Dim,
+        T: Zero,
+    {
+        unsafe { self.exclusive_scan::<E, Ps, Di, S, _>(scratch, T::zero(), |a, b| a + b) }
+    }
+
+    /// Scans the array round by round, each round covering one run of every
+    /// unit. An exclusive scan is an inclusive one that starts with `Some(init)`
+    /// as the carry and writes the accumulator before adding an element.
+    ///
+    /// # Safety
+    /// Must be called in a kernel uniform control flow state
+    unsafe fn scan<E, Ps, Di, S, F>(
+        &mut self,
+        scratch: &mut SimtArray<'_, Option<T>, Sc, Uninit, Dense, (S,)>,
+        init: Option<T>,
+        op: F,
+    ) where
+        E: UnitScope<Arch = Sc::Arch>,
+        Ps: ProjectionSetDim0<(D0,), E, Sc, Arch = Sc::Arch>,
+        Di: Distributes<D0>,
+        S: Dim,
+        F: FnMut(T, T) -> T,
+    {
+        let () = <Di as Distributes<D0>>::CHECK;
+        let units: usize = Ps::dim0().as_();
+        let idx: usize = Ps::idx0().as_();
+        assert!(
+            scratch.mapping.dim(0) >= units,
+            "scratch needs a slot per unit"
+        );
+        let len = self.mapping.dim(0);
+        let stride = self.mapping.stride(0);
+        let run_len = Di::run_len(len, units);
+        let rounds = len.div_ceil(run_len).div_ceil(units);
+        let exclusive = init.is_some();
+        let mut combine = combine_options(op);
+        let slot = |i: usize| unsafe { scratch.ptr.add(i) };
+        let mut carry = init;
+        for round in 0..rounds {
+            let start = ((round * units + idx) * run_len).min(len);
+            let run = start..(start + run_len).min(len);
+            let element = |i: usize| unsafe { self.ptr.offset(i as isize * stride) };
+            let total = run.clone().fold(None, |acc, i| {
+                combine(acc, Some(unsafe { element(i).read() }))
+            });
+            unsafe {
+                slot(idx).write(total);
+                Sc::sync();
+                // Inclusive scan of the run totals
+                let mut delta = 1;
+                while delta < units {
+                    let left = (idx >= delta).then(|| slot(idx - delta).read());
+                    Sc::sync();
+                    if let Some(left) = left {
+                        slot(idx).write(combine(left, slot(idx).read()));
+                    }
+                    Sc::sync();
+                    delta *= 2;
+                }
+                let mut acc = match idx {
+                    0 => carry,
+                    _ => combine(carry, slot(idx - 1).read()),
+                };
+                carry = combine(carry, slot(units - 1).read());
+                for i in run {
+                    let value = element(i).read();
+                    if exclusive {
+                        element(i).write(acc.unwrap_unchecked());
+                        acc = combine(acc, Some(value));
+                    } else {
+                        acc = combine(acc, Some(value));
+                        element(i).write(acc.unwrap_unchecked());
+                    }
+                }
+                // Nobody may overwrite the totals before every unit has read them
+                Sc::sync();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec;
+
+    use mdarray::Dense;
+
+    use crate::{
+        Blocked, Cyclic, GlobalIndex, Init, SimtArray, SyncableScope, Uninit,
+        host::{Block, Buffer, Thread, Xyz, launch},
+    };
+
+    #[test]
+    fn scan() {
+        let data = Buffer::new(vec![0u32; 21]);
+        let scratch = Buffer::new(vec![None::<u32>; 4]);
+        launch(1, 4, || {
+            let array = || unsafe {
+                data.array::<Block, Uninit, _>(0, (21,))
+                    .init_with::<Thread, (Xyz,), Cyclic, _>(|(GlobalIndex(i),)| i as u32 + 1)
+            };
+            let mut scratch = scratch.array::<Block, Uninit, _>(0, (4,));
+            let check = |array: &SimtArray<u32, Block, Init, Dense, (usize,)>, offset: u32| {
+                for i in 0..21 {
+                    let n = i as u32 + offset;
+                    assert_eq!(array.expr()[[i]], n * (n + 1) / 2);
+                }
+                unsafe { Block::sync() };
+            };
+            let mut a = array();
+            unsafe {
+                a.inclusive_sum::<Thread, (Xyz,), Cyclic, _>(&mut scratch);
+            }
+            check(&a, 1);
+            let mut a = array();
+            unsafe {
+                a.inclusive_sum::<Thread, (Xyz,), Blocked, _>(&mut scratch);
+            }
+            check(&a, 1);
+            let mut a = array();
+            unsafe {
+                a.exclusive_sum::<Thread, (Xyz,), Blocked, _>(&mut scratch);
+            }
+            check(&a, 0);
+            let mut a = array();
+            unsafe {
+                a.exclusive_scan::<Thread, (Xyz,), Cyclic, _, _>(&mut scratch, 0, |a, b| a + b)
+            };
+            check(&a, 0);
+        });
+    }
+}
@@ -0,0 +1,53 @@
+use core::{cell::Cell, marker::PhantomData};
+
+/// Number of `shared_array!` sites one kernel can take its buffers from.
+pub const MAX_SHARED_ARRAYS: usize = 16;
+
+/// Hands out the statically allocated shared memory buffers of the calling
+/// block, see `shared_array!`. Every expansion site of the macro returns its
+/// buffer only once per token, so that two arrays over the same buffer can
+/// never be alive in one thread. The arrays borrow the token.
+///
+/// A kernel creates the token once at its entry point and hands it down by
+/// reference. It is neither `Copy` nor `Send`.
+pub struct SharedMemory {
+    /// Keys of the sites that were taken
+    taken: [Cell<usize>; MAX_SHARED_ARRAYS],
+    len: Cell<usize>,
+    local: PhantomData<*const ()>,
+}
+
+impl SharedMemory {
+    /// # Safety
+    /// Must be called at most once per thread of a kernel, typically at its
+    /// entry point.
+    #[inline]
+    pub unsafe fn new() -> Self {
+        Self {
+            taken: [const { Cell::new(0) }; MAX_SHARED_ARRAYS],
+            len: Cell::new(0),
+            local: PhantomData,
+        }
+    }
+
+    /// Records that the buffer of the site `key` was handed out.
+    ///
+    /// # Panics
+    /// Panics if the site was taken before or if more than
+    /// [`MAX_SHARED_ARRAYS`] sites are taken.
+    #[doc(hidden)]
+    #[inline]
+    pub fn take(&self, key: usize) {
+        let len = self.len.get();
+        assert!(
+            self.taken[..len].iter().all(|taken| taken.get() != key),
+            "a `shared_array!` site was evaluated twice in one kernel"
+        );
+        assert!(
+            len < MAX_SHARED_ARRAYS,
+            "too many `shared_array!` sites in one kernel"
+        );
+        self.taken[len].set(key);
+        self.len.set(len + 1);
+    }
+}
@@ -0,0 +1,33 @@
+use core::marker::PhantomData;
+
+use crate::Scope;
+
+/// Proves that all units of scope `Sc` run the kernel together, e.g. that the
+/// grid was launched cooperatively. Operations that are only sound in such a
+/// launch take it by reference, see
+/// [`SimtArray::into_cooperative`](crate::SimtArray::into_cooperative).
+///
+/// A kernel creates the token once at its entry point and hands it down by
+/// reference. It is neither `Copy` nor `Send`. It does not prove uniform control
+/// flow though, as a reference can be used inside of any branch, so the
+/// operations that synchronize a scope stay `unsafe` and do not take it.
+pub struct Uniform<Sc> {
+    scope: PhantomData<Sc>,
+    local: PhantomData<*const ()>,
+}
+
+impl<Sc: Scope> Uniform<Sc> {
+    /// # Safety
+    /// Must be called by every unit of scope `Sc` in uniform control flow,
+    /// typically at the entry point of the kernel. The token and references to
+    /// it must only be used where control flow is uniform across `Sc`. For a
+    /// [`CooperativeScope`](crate::CooperativeScope) the kernel must have been
+    /// launched cooperatively.
+    #[inline]
+    pub unsafe fn new() -> Self {
+        Self {
+            scope: PhantomData,
+            local: PhantomData,
+        }
+    }
+}
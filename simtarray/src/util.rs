@@ -5,3 +5,16 @@ pub trait Sealed {}
 impl Sealed for Uninit {}
 impl Sealed for Init {}
 impl Sealed for FinallySplit {}
+
+/// Lifts `op` to the partial results of units, which are `None` if a unit had
+/// no elements. `None` is skipped, so only two of them combine to `None`.
+#[inline]
+pub fn combine_options<T>(
+    mut op: impl FnMut(T, T) -> T,
+) -> impl FnMut(Option<T>, Option<T>) -> Option<T> {
+    move |a, b| match (a, b) {
+        (Some(a), Some(b)) => Some(op(a, b)),
+        (a, None) => a,
+        (None, b) => b,
+    }
+}